@@ -0,0 +1,157 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A local cache of the fetched shipments, for offline use.
+
+use std::{collections::HashMap, fs, io, path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    carrier::Shipment,
+    file::{self, Lock},
+    state::{self, DataDirError},
+};
+
+/// The cache of fetched shipments.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache {
+    #[serde(default)]
+    shipments: HashMap<TrackingNumber, CachedShipment>,
+}
+
+/// A tracking number.
+type TrackingNumber = String;
+
+/// A shipment in the cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedShipment {
+    /// When the shipment has been fetched.
+    pub fetched_at: DateTime<Local>,
+    /// The shipment.
+    pub shipment: Shipment,
+}
+
+/// An error that can occur when loading the cache.
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("impossible to locate the cache file")]
+    NoDataDir(#[from] DataDirError),
+    #[error("error while reading the cache file")]
+    ReadError(#[from] io::Error),
+}
+
+/// An error that can occur when saving the cache.
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("impossible to locate the cache file")]
+    NoDataDir(#[from] DataDirError),
+    #[error("error while writing to the cache file")]
+    FsError(#[from] io::Error),
+}
+
+/// An error that can occur when locking the cache.
+#[derive(Debug, Error)]
+pub enum LockError {
+    #[error("impossible to locate the cache file")]
+    NoDataDir(#[from] DataDirError),
+    #[error("error while locking the cache file")]
+    FsError(#[from] io::Error),
+}
+
+/// The name of the cache file.
+const CACHE_FILE_NAME: &str = "cache.toml";
+
+/// The name of the lock file for the cache.
+const LOCK_FILE_NAME: &str = "cache.lock";
+
+impl Cache {
+    /// Loads the cache.
+    ///
+    /// As the cache can be rebuilt at any time, an invalid cache file—for
+    /// instance from another version of `track`—is considered empty.
+    pub fn load() -> Result<Self, LoadError> {
+        match fs::read_to_string(cache_file()?) {
+            Ok(cache) => Ok(toml::from_str(&cache).unwrap_or_default()),
+            Err(e) => match e.kind() {
+                io::ErrorKind::NotFound => Ok(Self::default()),
+                _ => Err(e.into()),
+            },
+        }
+    }
+
+    /// Locks the cache until the returned lock is dropped.
+    pub fn lock() -> Result<Lock, LockError> {
+        fs::create_dir_all(state::data_dir()?)?;
+        Ok(Lock::acquire(&state::data_dir()?.join(LOCK_FILE_NAME))?)
+    }
+
+    /// Saves the cache.
+    pub fn save(&self) -> Result<(), SaveError> {
+        fs::create_dir_all(state::data_dir()?)?;
+
+        let cache =
+            toml::to_string(self).expect("failed to serialise the cache");
+        file::write_atomic(&cache_file()?, &cache, None)?;
+
+        Ok(())
+    }
+
+    /// Gets a shipment from the cache.
+    pub fn get(&self, tracking_number: &str) -> Option<&CachedShipment> {
+        self.shipments.get(tracking_number)
+    }
+
+    /// Gets a shipment from the cache if it is not older than `max_age`.
+    pub fn get_fresh(
+        &self,
+        tracking_number: &str,
+        max_age: Duration,
+    ) -> Option<&CachedShipment> {
+        self.get(tracking_number)
+            .filter(|cached| cached.age() <= max_age)
+    }
+
+    /// Stores a freshly fetched shipment in the cache.
+    pub fn insert(&mut self, tracking_number: &str, shipment: Shipment) {
+        let cached = CachedShipment {
+            fetched_at: Local::now(),
+            shipment,
+        };
+
+        self.shipments.insert(tracking_number.to_owned(), cached);
+    }
+
+    /// Removes a shipment from the cache.
+    pub fn remove(&mut self, tracking_number: &str) -> Option<CachedShipment> {
+        self.shipments.remove(tracking_number)
+    }
+}
+
+impl CachedShipment {
+    /// Returns the time elapsed since the shipment has been fetched.
+    pub fn age(&self) -> Duration {
+        (Local::now() - self.fetched_at)
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+/// Gets the cache file for `track`.
+fn cache_file() -> Result<PathBuf, DataDirError> {
+    Ok(state::data_dir()?.join(CACHE_FILE_NAME))
+}
@@ -0,0 +1,405 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Carriers and their common shipment model.
+
+mod http;
+mod la_poste;
+
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::Config;
+
+pub use self::http::NewClientError;
+
+use self::la_poste::LaPoste;
+
+/// A carrier, from which tracking info can be retrieved.
+pub trait Carrier: Send + Sync {
+    /// Returns whether the tracking number is handled by the carrier.
+    fn identify(tracking_number: &str) -> bool
+    where
+        Self: Sized;
+
+    /// Retrieves the shipment data for a parcel.
+    fn get_shipment(
+        &self,
+        tracking_number: &str,
+    ) -> Result<Shipment, FetchError>;
+
+    /// Retrieves the shipment data for several parcels.
+    ///
+    /// At most `jobs` requests are in flight at the same time. The returned map
+    /// contains a result for each tracking number.
+    fn get_many(
+        &self,
+        tracking_numbers: &[&str],
+        jobs: NonZeroUsize,
+    ) -> ShipmentResults {
+        run_concurrently(tracking_numbers.chunks(1), jobs, |batch| {
+            batch
+                .iter()
+                .map(|&tracking_number| {
+                    (
+                        tracking_number.to_owned(),
+                        self.get_shipment(tracking_number),
+                    )
+                })
+                .collect()
+        })
+    }
+}
+
+/// The identifier of a carrier.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum CarrierId {
+    /// La Poste, including Colissimo and Chronopost.
+    #[default]
+    LaPoste,
+}
+
+/// A language in which tracking info can be requested.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+pub enum Lang {
+    /// French.
+    #[serde(rename = "fr_FR")]
+    #[value(name = "fr_FR")]
+    FrFr,
+    /// English.
+    #[serde(rename = "en_GB")]
+    #[value(name = "en_GB")]
+    EnGb,
+    /// German.
+    #[serde(rename = "de_DE")]
+    #[value(name = "de_DE")]
+    DeDe,
+    /// Spanish.
+    #[serde(rename = "es_ES")]
+    #[value(name = "es_ES")]
+    EsEs,
+    /// Italian.
+    #[serde(rename = "it_IT")]
+    #[value(name = "it_IT")]
+    ItIt,
+    /// Dutch.
+    #[serde(rename = "nl_NL")]
+    #[value(name = "nl_NL")]
+    NlNl,
+}
+
+/// A shipment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Shipment {
+    /// The product, like `colissimo`.
+    pub product: Option<String>,
+
+    /// The type of holder of the shipment, specific to the carrier.
+    pub holder: Option<u8>,
+
+    /// Whether the shipment has reached its final state.
+    pub is_final: bool,
+
+    /// Whether the shipment has been delivered.
+    #[serde(default)]
+    pub delivered: bool,
+
+    /// Whether the shipment has a problem, like a failed delivery or a return
+    /// to the sender.
+    #[serde(default)]
+    pub problem: bool,
+
+    /// The date of the delivery, or the estimated one.
+    pub delivery_date: Option<DateTime<Local>>,
+
+    /// The date the shipment has entered the network.
+    pub entry_date: Option<DateTime<Local>>,
+
+    /// The country from where the shipment has been sent.
+    pub origin_country: Option<String>,
+
+    /// The destination country.
+    pub arrival_country: Option<String>,
+
+    /// The delivery choice made by the recipient, specific to the carrier.
+    pub delivery_choice: Option<u8>,
+
+    /// The events, from the most recent to the oldest.
+    pub events: Vec<Event>,
+
+    /// The steps of the shipment, in order.
+    pub timeline: Vec<Step>,
+
+    /// The URL of the tracking page on the website of the carrier.
+    pub url: Option<String>,
+}
+
+impl Shipment {
+    /// Returns the most recent event.
+    pub fn latest_event(&self) -> Option<&Event> {
+        self.events.iter().max_by_key(|event| event.date)
+    }
+
+    /// Returns the last reached step of the timeline.
+    pub fn current_step(&self) -> Option<&Step> {
+        self.timeline.iter().rev().find(|step| step.reached)
+    }
+}
+
+/// An event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    /// The timestamp of the event.
+    pub date: DateTime<Local>,
+
+    /// A description of the event.
+    pub label: String,
+
+    /// The event code, specific to the carrier.
+    pub code: Option<String>,
+}
+
+/// A step in the timeline of a shipment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Step {
+    /// A description of the step.
+    pub label: String,
+
+    /// The date at which the step has been reached.
+    pub date: Option<DateTime<Local>>,
+
+    /// The country where the step has been reached.
+    pub country: Option<String>,
+
+    /// Whether the step has been reached.
+    pub reached: bool,
+
+    /// The type of step, specific to the carrier.
+    pub kind: Option<i8>,
+}
+
+/// An error that can occur when fetching the shipment data for a parcel.
+#[derive(Debug, Clone, Error)]
+pub enum FetchError {
+    #[error("unknown parcel")]
+    UnknownParcel(Option<String>),
+    #[error("invalid API key")]
+    InvalidApiKey(Option<String>),
+    #[error("invalid tracking number format")]
+    InvalidFormat(Option<String>),
+    #[error("too many requests")]
+    RateLimited,
+    #[error("the API server has encountered an error")]
+    ServerError(Option<String>),
+    #[error("unexpected response from the API ({0})")]
+    UnexpectedResponse(StatusCode, Option<String>),
+    #[error("error while sending the request")]
+    RequestError(#[source] Arc<reqwest::Error>),
+}
+
+/// The result of fetching several parcels, by tracking number.
+pub type ShipmentResults = HashMap<String, Result<Shipment, FetchError>>;
+
+impl CarrierId {
+    /// Identifies the carrier handling a tracking number.
+    pub fn identify(tracking_number: &str) -> Option<Self> {
+        Self::value_variants()
+            .iter()
+            .copied()
+            .find(|carrier| carrier.handles(tracking_number))
+    }
+
+    /// Returns whether the tracking number is handled by the carrier.
+    fn handles(self, tracking_number: &str) -> bool {
+        match self {
+            Self::LaPoste => LaPoste::identify(tracking_number),
+        }
+    }
+
+    /// Creates a client for the carrier.
+    ///
+    /// When `lang` is `None`, the tracking info is returned in the default
+    /// language of the carrier.
+    pub fn client(
+        self,
+        config: &Config,
+        lang: Option<Lang>,
+    ) -> Result<Box<dyn Carrier>, NewClientError> {
+        match self {
+            Self::LaPoste => Ok(Box::new(LaPoste::new(config, lang)?)),
+        }
+    }
+}
+
+impl Lang {
+    /// The environment variables defining the locale, by order of precedence.
+    const LOCALE_VARS: [&'static str; 3] = ["LC_ALL", "LC_MESSAGES", "LANG"];
+
+    /// Returns the language code, like `fr_FR`.
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::FrFr => "fr_FR",
+            Self::EnGb => "en_GB",
+            Self::DeDe => "de_DE",
+            Self::EsEs => "es_ES",
+            Self::ItIt => "it_IT",
+            Self::NlNl => "nl_NL",
+        }
+    }
+
+    /// Gets the language from the locale defined in the environment.
+    ///
+    /// Only the language part of the locale is considered, so that for
+    /// instance `en_US.UTF-8` gives English.
+    pub fn from_env() -> Option<Self> {
+        let locale = Self::LOCALE_VARS
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())?;
+
+        Self::from_locale(&locale)
+    }
+
+    /// Gets the language from a POSIX locale like `fr_FR.UTF-8`.
+    fn from_locale(locale: &str) -> Option<Self> {
+        let language = locale.split(['_', '.', '@']).next()?;
+
+        match language {
+            "fr" => Some(Self::FrFr),
+            "en" => Some(Self::EnGb),
+            "de" => Some(Self::DeDe),
+            "es" => Some(Self::EsEs),
+            "it" => Some(Self::ItIt),
+            "nl" => Some(Self::NlNl),
+            _ => None,
+        }
+    }
+}
+
+impl FetchError {
+    /// Builds an error from a status code and an optional message.
+    fn from_status(status: StatusCode, message: Option<String>) -> Self {
+        match status {
+            StatusCode::BAD_REQUEST => Self::InvalidFormat(message),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Self::InvalidApiKey(message)
+            }
+            StatusCode::NOT_FOUND => Self::UnknownParcel(message),
+            StatusCode::TOO_MANY_REQUESTS => Self::RateLimited,
+            status if status.is_server_error() => Self::ServerError(message),
+            status => Self::UnexpectedResponse(status, message),
+        }
+    }
+
+    /// Returns the message sent by the API, if any.
+    pub fn api_message(&self) -> Option<&str> {
+        match self {
+            Self::UnknownParcel(message)
+            | Self::InvalidApiKey(message)
+            | Self::InvalidFormat(message)
+            | Self::ServerError(message)
+            | Self::UnexpectedResponse(_, message) => message.as_deref(),
+            Self::RateLimited | Self::RequestError(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::RequestError(Arc::new(e))
+    }
+}
+
+/// Fetches the shipments of parcels handled by any carrier.
+pub fn get_all<'a>(
+    parcels: impl IntoIterator<Item = (&'a str, CarrierId)>,
+    config: &Config,
+    lang: Option<Lang>,
+    jobs: NonZeroUsize,
+) -> Result<ShipmentResults, NewClientError> {
+    let mut by_carrier = HashMap::<CarrierId, Vec<&str>>::new();
+    for (tracking_number, carrier) in parcels {
+        by_carrier.entry(carrier).or_default().push(tracking_number);
+    }
+
+    let mut results = ShipmentResults::new();
+    for (carrier, tracking_numbers) in by_carrier {
+        let client = carrier.client(config, lang)?;
+        results.extend(client.get_many(&tracking_numbers, jobs));
+    }
+
+    Ok(results)
+}
+
+/// Fetches batches of parcels with at most `jobs` batches in flight.
+fn run_concurrently<'a, I, F>(
+    batches: I,
+    jobs: NonZeroUsize,
+    fetch: F,
+) -> ShipmentResults
+where
+    I: Iterator<Item = &'a [&'a str]> + Send,
+    F: Fn(&[&str]) -> ShipmentResults + Sync,
+{
+    let batches = Mutex::new(batches);
+    let results = Mutex::new(ShipmentResults::new());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.get() {
+            scope.spawn(|| loop {
+                let batch = batches
+                    .lock()
+                    .expect("the batch iterator lock is poisoned")
+                    .next();
+
+                let Some(batch) = batch else { break };
+                let batch_results = fetch(batch);
+
+                results
+                    .lock()
+                    .expect("the result map lock is poisoned")
+                    .extend(batch_results);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .expect("the result map lock is poisoned")
+}
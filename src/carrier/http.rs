@@ -0,0 +1,194 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! An HTTP client shared by the carriers.
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::{
+    blocking::Response,
+    header::{self, HeaderMap, InvalidHeaderValue},
+    Certificate, IntoUrl, NoProxy, Proxy, StatusCode,
+};
+use thiserror::Error;
+
+use crate::config::Config;
+
+/// An HTTP client with timeouts and retries.
+#[derive(Debug)]
+pub struct HttpClient {
+    reqwest_client: reqwest::blocking::Client,
+    retry_policy: RetryPolicy,
+}
+
+/// A policy for retrying requests on transient errors.
+#[derive(Debug)]
+struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+}
+
+/// An error that can occur when creating a new client.
+#[derive(Debug, Error)]
+pub enum NewClientError {
+    #[error("invalid API key")]
+    InvalidApiKey(#[from] InvalidHeaderValue),
+    #[error("invalid API base URL: {0}")]
+    InvalidApiBaseUrl(String),
+    #[error("invalid proxy: {0}")]
+    InvalidProxy(String, #[source] reqwest::Error),
+    #[error("impossible to read the CA certificates from {0}")]
+    ReadCaCerts(PathBuf, #[source] io::Error),
+    #[error("no valid CA certificate found in {0}")]
+    InvalidCaCerts(PathBuf),
+    #[error("impossible to create a client")]
+    ClientBuilderError(#[from] reqwest::Error),
+}
+
+/// The maximum delay to wait before retrying a request.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+impl HttpClient {
+    /// Creates a new `HttpClient` sending the given headers with each request.
+    pub fn new(
+        config: &Config,
+        headers: HeaderMap,
+    ) -> Result<Self, NewClientError> {
+        let mut builder = reqwest::blocking::Client::builder()
+            .default_headers(headers)
+            .connect_timeout(config.connect_timeout())
+            .timeout(config.timeout());
+
+        // Without an explicit proxy, reqwest uses the one defined by the
+        // standard `HTTPS_PROXY` and `NO_PROXY` environment variables.
+        if let Some(proxy) = config.proxy() {
+            let proxy = Proxy::all(proxy)
+                .map_err(|e| NewClientError::InvalidProxy(proxy.to_owned(), e))?
+                .no_proxy(NoProxy::from_env());
+            builder = builder.proxy(proxy);
+        }
+
+        if let Some(path) = config.extra_ca_certs() {
+            for certificate in read_ca_certs(path)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+
+        let reqwest_client = builder.build()?;
+
+        let retry_policy = RetryPolicy {
+            max_retries: config.max_retries(),
+            base_delay: config.retry_delay(),
+        };
+
+        Ok(Self {
+            reqwest_client,
+            retry_policy,
+        })
+    }
+
+    /// Sends a GET request, retrying on transient errors.
+    ///
+    /// Timeouts, connection errors, server errors and rate limiting are
+    /// retried with an exponential backoff. When the server sends a
+    /// `Retry-After` header, its delay is honoured instead.
+    pub fn get(
+        &self,
+        url: impl IntoUrl + Clone,
+    ) -> Result<Response, reqwest::Error> {
+        let mut retry = 0;
+
+        loop {
+            let result = self.reqwest_client.get(url.clone()).send();
+
+            if retry >= self.retry_policy.max_retries {
+                return result;
+            }
+
+            let delay = match &result {
+                Ok(response) if is_transient(response.status()) => {
+                    retry_after(response)
+                        .unwrap_or_else(|| self.retry_policy.backoff(retry))
+                }
+                Err(e) if e.is_timeout() || e.is_connect() => {
+                    self.retry_policy.backoff(retry)
+                }
+                _ => return result,
+            };
+
+            thread::sleep(delay.min(MAX_RETRY_DELAY));
+            retry += 1;
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns the delay before a retry, with exponential backoff and jitter.
+    ///
+    /// The delay is doubled at each retry, then a random value between its half
+    /// and itself is picked so that concurrent clients do not retry in sync.
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(retry))
+            .min(MAX_RETRY_DELAY);
+        rand::thread_rng().gen_range(delay / 2..=delay)
+    }
+}
+
+/// Returns whether a status is worth retrying the request.
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Returns the delay from the `Retry-After` header of a response, if any.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?;
+
+    match value.parse() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+        }
+    }
+}
+
+/// Reads CA certificates from a PEM file.
+fn read_ca_certs(path: &Path) -> Result<Vec<Certificate>, NewClientError> {
+    let read_error = |e| NewClientError::ReadCaCerts(path.to_owned(), e);
+
+    let file = File::open(path).map_err(read_error)?;
+    let certificates =
+        rustls_pemfile::certs(&mut BufReader::new(file)).map_err(read_error)?;
+
+    if certificates.is_empty() {
+        return Err(NewClientError::InvalidCaCerts(path.to_owned()));
+    }
+
+    certificates
+        .iter()
+        .map(|der| Certificate::from_der(der))
+        .collect::<Result<_, _>>()
+        .map_err(|_| NewClientError::InvalidCaCerts(path.to_owned()))
+}
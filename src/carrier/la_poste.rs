@@ -0,0 +1,646 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A quick-and-dirty client for the La Poste “Suivi v2” API.
+
+use std::num::NonZeroUsize;
+
+use chrono::{DateTime, Local};
+use reqwest::{
+    blocking::Response,
+    header::{self, HeaderMap, HeaderValue},
+    StatusCode, Url,
+};
+use serde::Deserialize;
+
+use crate::{config::Config, tracking_number};
+
+use super::{
+    self as carrier,
+    http::{HttpClient, NewClientError},
+    run_concurrently, Carrier, FetchError, Lang, ShipmentResults,
+};
+
+/// A La Poste “Suivi v2” API client.
+#[derive(Debug)]
+pub struct LaPoste {
+    http_client: HttpClient,
+    endpoint: Url,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackingInfo {
+    shipment: Shipment,
+}
+
+/// A shipment, as returned by the API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Shipment {
+    /// The tracking number.
+    id_ship: String,
+
+    /// The type of holder of the shipment.
+    holder: Option<u8>,
+
+    /// The product, like `colissimo`, `lettre` or `chronopost`.
+    product: Option<String>,
+
+    /// Whether the shipment has reached its final state.
+    #[serde(default)]
+    is_final: bool,
+
+    /// The date of the delivery, or the estimated one.
+    delivery_date: Option<DateTime<Local>>,
+
+    /// The date the shipment has entered the network.
+    entry_date: Option<DateTime<Local>>,
+
+    /// The events, from the most recent to the oldest.
+    #[serde(default, rename = "event")]
+    events: Vec<Event>,
+
+    /// The steps of the shipment.
+    #[serde(default)]
+    timeline: Vec<TimelineStep>,
+
+    /// Additional data about the shipment.
+    context_data: Option<ContextData>,
+
+    /// The URL of the tracking page on laposte.fr.
+    url: Option<String>,
+}
+
+/// An event, as returned by the API.
+#[derive(Debug, Deserialize)]
+struct Event {
+    /// The event code.
+    code: Option<String>,
+
+    /// The timestamp of the event.
+    date: DateTime<Local>,
+
+    /// A description of the event.
+    label: String,
+}
+
+/// A step in the timeline of a shipment, as returned by the API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TimelineStep {
+    /// A short description of the step.
+    short_label: Option<String>,
+
+    /// A long description of the step.
+    long_label: Option<String>,
+
+    /// The date at which the step has been reached.
+    date: Option<DateTime<Local>>,
+
+    /// The country where the step has been reached.
+    country: Option<String>,
+
+    /// Whether the step has been reached.
+    #[serde(default)]
+    status: bool,
+
+    /// The type of step.
+    #[serde(rename = "type")]
+    kind: Option<i8>,
+}
+
+/// Additional data about a shipment, as returned by the API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContextData {
+    /// The country from where the shipment has been sent.
+    origin_country: Option<String>,
+
+    /// The destination country.
+    arrival_country: Option<String>,
+
+    /// The delivery choice made by the recipient.
+    delivery_choice: Option<DeliveryChoice>,
+}
+
+/// A delivery choice, as returned by the API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DeliveryChoice {
+    /// The delivery choice code.
+    delivery_choice: Option<u8>,
+}
+
+/// An error response from the API.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorResponse {
+    return_code: Option<u16>,
+    #[serde(alias = "message")]
+    return_message: Option<String>,
+}
+
+/// A response from the API for several tracking numbers.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BatchResponse {
+    Many(Vec<BatchItem>),
+    One(Box<BatchItem>),
+}
+
+/// The response for one tracking number inside a `BatchResponse`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BatchItem {
+    return_code: Option<u16>,
+    #[serde(alias = "message")]
+    return_message: Option<String>,
+    id_ship: Option<String>,
+    shipment: Option<Shipment>,
+}
+
+/// The path of the API endpoint, relative to the API base URL.
+const API_ENDPOINT_PATH: &str = "idships/";
+
+/// The maximum number of tracking numbers the API accepts in one request.
+const MAX_IDS_PER_REQUEST: usize = 10;
+
+/// The prefix of the codes of delivery events, as in “DI1: distribué”.
+const DELIVERY_EVENT_CODE_PREFIX: &str = "DI";
+
+/// The codes of events reporting a problem: “PB1: problème en cours”, “ND1:
+/// non distribuable” and “RE1: retourné à l’expéditeur”.
+const PROBLEM_EVENT_CODES: &[&str] = &["PB1", "ND1", "RE1"];
+
+impl LaPoste {
+    /// Creates a new La Poste client.
+    ///
+    /// When `lang` is `None`, the tracking info is returned in the default
+    /// language of the API.
+    pub fn new(
+        config: &Config,
+        lang: Option<Lang>,
+    ) -> Result<Self, NewClientError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            HeaderValue::from_static("application/json"),
+        );
+        if let Some(lang) = lang {
+            headers.insert(
+                header::ACCEPT_LANGUAGE,
+                HeaderValue::from_static(lang.code()),
+            );
+        }
+        headers.insert("X-Okapi-Key", config.api_key().parse()?);
+
+        let api_base_url = config.api_base_url();
+        let endpoint =
+            Url::parse(&format!("{}/", api_base_url.trim_end_matches('/')))
+                .and_then(|base_url| base_url.join(API_ENDPOINT_PATH))
+                .map_err(|_| NewClientError::InvalidApiBaseUrl(api_base_url))?;
+
+        let http_client = HttpClient::new(config, headers)?;
+
+        Ok(Self {
+            http_client,
+            endpoint,
+        })
+    }
+
+    /// Retrieves the shipment data for a batch of parcels.
+    fn get_batch(&self, tracking_numbers: &[&str]) -> ShipmentResults {
+        match self.send_batch(tracking_numbers) {
+            Ok(items) => map_batch_items(tracking_numbers, items),
+            Err(e) => tracking_numbers
+                .iter()
+                .map(|&tracking_number| {
+                    (tracking_number.to_owned(), Err(e.clone()))
+                })
+                .collect(),
+        }
+    }
+
+    /// Sends a request for a batch of parcels.
+    fn send_batch(
+        &self,
+        tracking_numbers: &[&str],
+    ) -> Result<Vec<BatchItem>, FetchError> {
+        let response = self.send(&tracking_numbers.join(","))?;
+        let status = response.status();
+
+        match response.json() {
+            Ok(response) => batch_items(response, status),
+            Err(_) if !status.is_success() => {
+                Err(FetchError::from_status(status, None))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Sends a GET request for tracking numbers.
+    fn send(&self, tracking_numbers: &str) -> Result<Response, reqwest::Error> {
+        let mut url = self.endpoint.clone();
+        url.path_segments_mut()
+            .expect("the API base URL is a valid base")
+            .pop_if_empty()
+            .push(tracking_numbers);
+
+        self.http_client.get(url)
+    }
+}
+
+impl Carrier for LaPoste {
+    fn identify(tracking_number: &str) -> bool {
+        // La Poste handles all the formats known by `track`.
+        tracking_number::detect(&tracking_number::normalise(tracking_number))
+            .is_some()
+    }
+
+    fn get_shipment(
+        &self,
+        tracking_number: &str,
+    ) -> Result<carrier::Shipment, FetchError> {
+        let response = self.send(tracking_number)?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response));
+        }
+
+        let tracking_info: TrackingInfo = response.json()?;
+        Ok(tracking_info.shipment.into())
+    }
+
+    fn get_many(
+        &self,
+        tracking_numbers: &[&str],
+        jobs: NonZeroUsize,
+    ) -> ShipmentResults {
+        run_concurrently(
+            tracking_numbers.chunks(MAX_IDS_PER_REQUEST),
+            jobs,
+            |batch| self.get_batch(batch),
+        )
+    }
+}
+
+impl From<Shipment> for carrier::Shipment {
+    fn from(shipment: Shipment) -> Self {
+        let (origin_country, arrival_country, delivery_choice) =
+            match shipment.context_data {
+                Some(context_data) => (
+                    context_data.origin_country,
+                    context_data.arrival_country,
+                    context_data
+                        .delivery_choice
+                        .and_then(|choice| choice.delivery_choice),
+                ),
+                None => (None, None, None),
+            };
+
+        // Only the latest event matters, as a problem can be resolved later.
+        let problem = shipment
+            .events
+            .iter()
+            .max_by_key(|event| event.date)
+            .and_then(|event| event.code.as_deref())
+            .is_some_and(|code| PROBLEM_EVENT_CODES.contains(&code));
+
+        // `isFinal` is also set for returned or undeliverable parcels, so only
+        // a delivery event tells the parcel has been delivered.
+        let delivered = !problem
+            && shipment.events.iter().any(|event| {
+                event.code.as_deref().is_some_and(|code| {
+                    code.starts_with(DELIVERY_EVENT_CODE_PREFIX)
+                })
+            });
+
+        Self {
+            product: shipment.product,
+            holder: shipment.holder,
+            is_final: shipment.is_final,
+            delivered,
+            problem,
+            delivery_date: shipment.delivery_date,
+            entry_date: shipment.entry_date,
+            origin_country,
+            arrival_country,
+            delivery_choice,
+            events: shipment.events.into_iter().map(Into::into).collect(),
+            timeline: shipment.timeline.into_iter().map(Into::into).collect(),
+            url: shipment.url,
+        }
+    }
+}
+
+impl From<Event> for carrier::Event {
+    fn from(event: Event) -> Self {
+        Self {
+            date: event.date,
+            label: event.label,
+            code: event.code,
+        }
+    }
+}
+
+impl From<TimelineStep> for carrier::Step {
+    fn from(step: TimelineStep) -> Self {
+        Self {
+            label: step.short_label.or(step.long_label).unwrap_or_default(),
+            date: step.date,
+            country: step.country,
+            reached: step.status,
+            kind: step.kind,
+        }
+    }
+}
+
+/// Gets the items of a batch response.
+///
+/// A single object without a shipment is an error for the whole batch.
+fn batch_items(
+    response: BatchResponse,
+    status: StatusCode,
+) -> Result<Vec<BatchItem>, FetchError> {
+    match response {
+        BatchResponse::Many(items) => Ok(items),
+        BatchResponse::One(item) if item.shipment.is_some() => Ok(vec![*item]),
+        BatchResponse::One(item) => Err(error_from_api(
+            item.return_code,
+            item.return_message,
+            status,
+        )),
+    }
+}
+
+/// Maps the items of a batch response to their tracking number.
+fn map_batch_items(
+    tracking_numbers: &[&str],
+    items: Vec<BatchItem>,
+) -> ShipmentResults {
+    let mut results = ShipmentResults::new();
+
+    for item in items {
+        let id_ship = item
+            .shipment
+            .as_ref()
+            .map(|shipment| shipment.id_ship.as_str())
+            .or(item.id_ship.as_deref());
+
+        let Some(tracking_number) = id_ship.and_then(|id_ship| {
+            tracking_numbers.iter().find(|tracking_number| {
+                tracking_number.eq_ignore_ascii_case(id_ship)
+            })
+        }) else {
+            continue;
+        };
+
+        let result = match item.shipment {
+            Some(shipment) => Ok(shipment.into()),
+            None => Err(error_from_api(
+                item.return_code,
+                item.return_message,
+                StatusCode::NOT_FOUND,
+            )),
+        };
+
+        results.insert((*tracking_number).to_owned(), result);
+    }
+
+    for tracking_number in tracking_numbers {
+        results
+            .entry((*tracking_number).to_owned())
+            .or_insert(Err(FetchError::UnknownParcel(None)));
+    }
+
+    results
+}
+
+/// Builds an error from an unsuccessful API response.
+fn error_from_response(response: Response) -> FetchError {
+    let status = response.status();
+    let ErrorResponse {
+        return_code,
+        return_message,
+    } = response.json().unwrap_or(ErrorResponse {
+        return_code: None,
+        return_message: None,
+    });
+
+    error_from_api(return_code, return_message, status)
+}
+
+/// Builds an error from the return code and message sent by the API.
+///
+/// The status is used when there is no valid return code.
+fn error_from_api(
+    return_code: Option<u16>,
+    return_message: Option<String>,
+    status: StatusCode,
+) -> FetchError {
+    let status = return_code
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or(status);
+
+    FetchError::from_status(status, return_message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> BatchResponse {
+        serde_json::from_str(json).expect("valid JSON")
+    }
+
+    fn shipment_json(id_ship: &str) -> String {
+        format!(
+            r#"{{
+                "returnCode": 200,
+                "shipment": {{
+                    "idShip": "{id_ship}",
+                    "isFinal": false,
+                    "event": [{{
+                        "code": "PC1",
+                        "date": "2023-05-30T10:00:00+02:00",
+                        "label": "Pris en charge"
+                    }}]
+                }}
+            }}"#
+        )
+    }
+
+    fn map(tracking_numbers: &[&str], json: &str) -> ShipmentResults {
+        let items = batch_items(parse(json), StatusCode::MULTI_STATUS)
+            .expect("a list of items");
+        map_batch_items(tracking_numbers, items)
+    }
+
+    #[test]
+    fn mixed_batch_maps_shipments_and_errors() {
+        let json = format!(
+            r#"[
+                {},
+                {{
+                    "returnCode": 404,
+                    "returnMessage": "Numéro introuvable",
+                    "idShip": "8K00000000001"
+                }},
+                {{
+                    "returnCode": 400,
+                    "returnMessage": "Format invalide",
+                    "idShip": "XX"
+                }}
+            ]"#,
+            shipment_json("6A12345678901")
+        );
+
+        let results = map(&["6A12345678901", "8K00000000001", "XX"], &json);
+
+        assert_eq!(results.len(), 3);
+        let shipment = results["6A12345678901"].as_ref().expect("a shipment");
+        assert_eq!(shipment.events[0].label, "Pris en charge");
+        assert!(matches!(
+            &results["8K00000000001"],
+            Err(FetchError::UnknownParcel(Some(message)))
+                if message == "Numéro introuvable"
+        ));
+        assert!(matches!(
+            &results["XX"],
+            Err(FetchError::InvalidFormat(Some(_)))
+        ));
+    }
+
+    #[test]
+    fn id_ship_is_matched_case_insensitively() {
+        let json = format!("[{}]", shipment_json("6a12345678901"));
+
+        let results = map(&["6A12345678901"], &json);
+
+        assert!(results["6A12345678901"].is_ok());
+        assert!(!results.contains_key("6a12345678901"));
+    }
+
+    #[test]
+    fn missing_item_is_an_unknown_parcel() {
+        let json = format!("[{}]", shipment_json("6A12345678901"));
+
+        let results = map(&["6A12345678901", "8K00000000001"], &json);
+
+        assert!(results["6A12345678901"].is_ok());
+        assert!(matches!(
+            results["8K00000000001"],
+            Err(FetchError::UnknownParcel(None))
+        ));
+    }
+
+    #[test]
+    fn single_shipment_is_a_batch_of_one() {
+        let json = shipment_json("6A12345678901");
+
+        let results = map(&["6A12345678901"], &json);
+
+        assert!(results["6A12345678901"].is_ok());
+    }
+
+    #[test]
+    fn single_error_object_fails_the_whole_batch() {
+        let json = r#"{"returnCode": 401, "returnMessage": "Invalid key"}"#;
+
+        let result = batch_items(parse(json), StatusCode::UNAUTHORIZED);
+
+        assert!(matches!(
+            result,
+            Err(FetchError::InvalidApiKey(Some(message))) if message == "Invalid key"
+        ));
+    }
+
+    #[test]
+    fn single_error_object_without_code_uses_the_status() {
+        let json = r#"{"message": "Oops"}"#;
+
+        let result = batch_items(parse(json), StatusCode::SERVICE_UNAVAILABLE);
+
+        assert!(matches!(result, Err(FetchError::ServerError(Some(_)))));
+    }
+
+    fn shipment_with_events(is_final: bool, codes: &[&str]) -> Shipment {
+        let events = codes
+            .iter()
+            .enumerate()
+            .map(|(day, code)| {
+                format!(
+                    r#"{{
+                        "code": "{code}",
+                        "date": "2023-05-{:02}T10:00:00+02:00",
+                        "label": "{code}"
+                    }}"#,
+                    day + 1
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let json = format!(
+            r#"{{
+                "idShip": "6A12345678901",
+                "isFinal": {is_final},
+                "event": [{events}]
+            }}"#
+        );
+        serde_json::from_str(&json).expect("a valid shipment")
+    }
+
+    #[test]
+    fn returned_shipment_is_a_problem() {
+        let shipment: carrier::Shipment =
+            shipment_with_events(true, &["PC1", "ET1", "RE1"]).into();
+
+        assert!(shipment.problem);
+    }
+
+    #[test]
+    fn returned_shipment_is_not_delivered() {
+        let shipment: carrier::Shipment =
+            shipment_with_events(true, &["PC1", "DI1", "RE1"]).into();
+
+        assert!(shipment.is_final);
+        assert!(!shipment.delivered);
+    }
+
+    #[test]
+    fn final_shipment_without_delivery_event_is_not_delivered() {
+        let shipment: carrier::Shipment =
+            shipment_with_events(true, &["PC1", "ET1"]).into();
+
+        assert!(!shipment.delivered);
+    }
+
+    #[test]
+    fn resolved_problem_is_not_a_problem() {
+        let shipment: carrier::Shipment =
+            shipment_with_events(false, &["PC1", "PB1", "PB2"]).into();
+
+        assert!(!shipment.problem);
+    }
+
+    #[test]
+    fn delivered_shipment_is_not_a_problem() {
+        let shipment: carrier::Shipment =
+            shipment_with_events(true, &["PC1", "ND1", "DI1"]).into();
+
+        assert!(shipment.delivered);
+        assert!(!shipment.problem);
+    }
+}
@@ -15,30 +15,77 @@
 
 mod add;
 mod all;
+mod archive;
+mod color;
 mod helpers;
 mod info;
 mod init;
 mod list;
+mod prune;
 mod remove;
+mod template;
+mod watch;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use eyre::Result;
 
-use crate::{config, error, hint};
+use crate::{
+    carrier::{FetchError, Lang, NewClientError},
+    config::{self, Config},
+    dates::{DateFormat, DateFormatter},
+    error, hint,
+    notifier::NotifierError,
+    output::Format,
+    state,
+    tracking_number::ValidationError,
+    views::user_templates,
+};
 
 use self::{
     add::Add,
-    all::All,
-    info::Info,
+    all::{All, AllError},
+    archive::{Archive, ArchiveError},
+    color::ColorChoice,
+    info::{Info, InfoError},
     init::{Init, InitError},
     list::List,
+    prune::Prune,
     remove::{Remove, RemoveError},
+    template::{Template, TemplateError},
+    watch::Watch,
 };
 
 /// A quick-and-dirty CLI tool for tracking parcels.
 #[derive(Debug, Parser)]
 #[command(author, version = env!("VERSION_WITH_GIT"))]
-pub enum Track {
+pub struct Track {
+    #[command(flatten)]
+    options: GlobalOptions,
+    #[command(subcommand)]
+    command: TrackCommand,
+}
+
+/// Options available for all commands.
+#[derive(Debug, Args)]
+struct GlobalOptions {
+    /// The language of the tracking info.
+    #[arg(long, global = true)]
+    lang: Option<Lang>,
+    /// The output format of `info`, `all` and `list`.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    format: Format,
+    /// When to use colours.
+    #[arg(long, global = true, value_enum, default_value_t)]
+    color: ColorChoice,
+    /// The format of dates: `rfc2822`, `relative`, `iso8601` or a strftime
+    /// pattern.
+    #[arg(long, global = true)]
+    date_format: Option<DateFormat>,
+}
+
+/// The commands of `track`.
+#[derive(Debug, Subcommand)]
+enum TrackCommand {
     /// Initialise the configuration.
     Init(Init),
     /// Retrieve and prints tracking info for a parcel.
@@ -51,23 +98,38 @@ pub enum Track {
     Remove(Remove),
     /// Retrieve and prints tracking info for all tracked parcels.
     All(All),
+    /// Watch the tracked parcels and run hooks on new events.
+    Watch(Watch),
+    /// Manage the archived parcels.
+    Archive(Archive),
+    /// Remove parcels from the archive.
+    Prune(Prune),
+    /// Manage the user templates.
+    Template(Template),
 }
 
 trait Command {
     /// Runs the command.
-    fn run(&self) -> Result<()>;
+    fn run(&self, options: &GlobalOptions) -> Result<()>;
 }
 
 impl Track {
     /// Runs track.
     pub fn run() -> Result<()> {
-        let result = match Self::parse() {
-            Self::Init(init) => init.run(),
-            Self::Info(info) => info.run(),
-            Self::List(list) => list.run(),
-            Self::Add(add) => add.run(),
-            Self::Remove(remove) => remove.run(),
-            Self::All(all) => all.run(),
+        let Self { options, command } = Self::parse();
+        options.color.apply()?;
+
+        let result = match command {
+            TrackCommand::Init(init) => init.run(&options),
+            TrackCommand::Info(info) => info.run(&options),
+            TrackCommand::List(list) => list.run(&options),
+            TrackCommand::Add(add) => add.run(&options),
+            TrackCommand::Remove(remove) => remove.run(&options),
+            TrackCommand::All(all) => all.run(&options),
+            TrackCommand::Watch(watch) => watch.run(&options),
+            TrackCommand::Archive(archive) => archive.run(&options),
+            TrackCommand::Prune(prune) => prune.run(&options),
+            TrackCommand::Template(template) => template.run(&options),
         };
 
         match result {
@@ -77,11 +139,56 @@ impl Track {
     }
 }
 
+impl GlobalOptions {
+    /// Returns the language to use for the tracking info.
+    ///
+    /// The `--lang` option takes precedence over the configuration, which takes
+    /// precedence over the locale from the environment.
+    fn lang(&self, config: &Config) -> Option<Lang> {
+        self.lang.or_else(|| config.lang()).or_else(Lang::from_env)
+    }
+
+    /// Returns the formatter for the dates in the tracking info.
+    ///
+    /// The `--date-format` option takes precedence over the configuration, if
+    /// any.
+    fn date_formatter(&self, config: Option<&Config>) -> DateFormatter {
+        let format = self
+            .date_format
+            .as_ref()
+            .or_else(|| config.and_then(Config::date_format))
+            .cloned()
+            .unwrap_or_default();
+
+        DateFormatter::new(format, config.and_then(Config::timezone))
+    }
+
+    /// Returns the formatter for the dates, for commands working offline.
+    ///
+    /// The configuration is only loaded for the date settings, so that these
+    /// commands still work without it.
+    fn offline_date_formatter(&self) -> DateFormatter {
+        self.date_formatter(Config::load().ok().as_ref())
+    }
+}
+
 fn handle_errors(e: color_eyre::Report) -> Result<()> {
-    if e.downcast_ref::<config::LoadError>().is_some() {
+    if let Some(config::LoadError::NewerVersion { found, supported }) =
+        e.downcast_ref::<config::LoadError>()
+    {
+        error!("The configuration is from a newer version of track (version {found}, supported up to {supported}).");
+        hint!("Please upgrade track to the latest version.");
+        std::process::exit(1);
+    } else if e.downcast_ref::<config::LoadError>().is_some() {
         error!("The configuration is absent or invalid.");
         hint!("You can create a configuration by running `track init`.");
         std::process::exit(1);
+    } else if let Some(state::LoadError::NewerVersion { found, supported }) =
+        e.downcast_ref::<state::LoadError>()
+    {
+        error!("The state is from a newer version of track (version {found}, supported up to {supported}).");
+        hint!("Please upgrade track to the latest version.");
+        std::process::exit(1);
     } else if let Some(e) = e.downcast_ref::<InitError>() {
         match e {
             InitError::ExistingConfig => {
@@ -90,10 +197,163 @@ fn handle_errors(e: color_eyre::Report) -> Result<()> {
             }
         }
         std::process::exit(1);
+    } else if let Some(e) = e.downcast_ref::<ArchiveError>() {
+        error!("{e}");
+        hint!("You can list the archived parcels by running `track archive list`.");
+        std::process::exit(1);
+    } else if let Some(e) = e.downcast_ref::<TemplateError>() {
+        error!("{e}");
+        hint!("You can overwrite it by running `track template dump -f`.");
+        std::process::exit(1);
+    } else if let Some(load_error) =
+        e.downcast_ref::<user_templates::LoadError>()
+    {
+        if !print_template_load_error(load_error) {
+            return Err(e);
+        }
+        std::process::exit(1);
+    } else if let Some(user_templates::RenderError::UserTemplateError(
+        name,
+        source,
+    )) = e.downcast_ref::<user_templates::RenderError>()
+    {
+        error!("The template {name} could not be rendered: {source:#}");
+        hint!("You can restore the default one by running `track template dump -f`.");
+        std::process::exit(1);
     } else if let Some(e) = e.downcast_ref::<RemoveError>() {
         error!("{e}");
         std::process::exit(1);
+    } else if let Some(e) = e.downcast_ref::<ValidationError>() {
+        error!("{e}");
+        if !matches!(e, ValidationError::Empty) {
+            hint!("If you are sure of it, you can bypass the validation by running `track add --no-validate`.");
+        }
+        std::process::exit(1);
+    } else if let Some(e) = e.downcast_ref::<InfoError>() {
+        error!("{e}");
+        hint!("You can fetch it by running `track info` without `--cached`.");
+        std::process::exit(1);
+    } else if let Some(e) = e.downcast_ref::<NotifierError>() {
+        match e {
+            #[cfg(not(feature = "notifications"))]
+            NotifierError::Unsupported => {
+                error!("Desktop notifications are not supported by this build of track.");
+                hint!("You can enable them by building track with `--features notifications`.");
+            }
+            #[cfg(feature = "notifications")]
+            NotifierError::ConnectionError(source)
+            | NotifierError::NotifyError(source) => {
+                error!(
+                    "Impossible to reach the notification server: {source}."
+                );
+                hint!("Desktop notifications need a D-Bus session bus, usually provided by your desktop environment.");
+            }
+        }
+        std::process::exit(1);
+    } else if let Some(e) = e.downcast_ref::<AllError>() {
+        error!("{e}");
+        if matches!(e, AllError::NotCached(_)) {
+            hint!(
+                "You can fetch them by running `track all` without `--cached`."
+            );
+        }
+        std::process::exit(1);
+    } else if let Some(new_client_error) = e.downcast_ref::<NewClientError>() {
+        if !print_new_client_error(new_client_error) {
+            return Err(e);
+        }
+        std::process::exit(1);
+    } else if let Some(fetch_error) = e.downcast_ref::<FetchError>() {
+        if !print_fetch_error(fetch_error) {
+            return Err(e);
+        }
+        std::process::exit(1);
     } else {
         Err(e)
     }
 }
+
+/// Prints a message and a hint for user template errors.
+///
+/// Returns `false` if the error is not expected to happen, in which case it
+/// should be reported with all its details.
+fn print_template_load_error(e: &user_templates::LoadError) -> bool {
+    match e {
+        user_templates::LoadError::ReadError(path, source) => {
+            error!(
+                "The template {} could not be read: {source}.",
+                path.display()
+            );
+        }
+        user_templates::LoadError::InvalidTemplate(path, source) => {
+            error!("The template {} is invalid: {source:#}", path.display());
+            hint!("You can restore the default one by running `track template dump -f`.");
+        }
+        user_templates::LoadError::NoConfigDir(_) => return false,
+    }
+
+    true
+}
+
+/// Prints a message and a hint for client configuration errors.
+///
+/// Returns `false` if the error is not expected to happen, in which case it
+/// should be reported with all its details.
+fn print_new_client_error(e: &NewClientError) -> bool {
+    match e {
+        NewClientError::InvalidApiKey(_) => {
+            error!("The API key is not valid.");
+            hint!("You can set a new API key by running `track init -f`.");
+        }
+        NewClientError::InvalidApiBaseUrl(_)
+        | NewClientError::InvalidProxy(..) => {
+            error!("The configuration is invalid: {e}.");
+            hint!("Please check the configuration file.");
+        }
+        NewClientError::ReadCaCerts(..) | NewClientError::InvalidCaCerts(_) => {
+            error!("The configuration is invalid: {e}.");
+            hint!("Please check `extra_ca_certs` in the configuration file.");
+        }
+        NewClientError::ClientBuilderError(_) => return false,
+    }
+
+    true
+}
+
+/// Prints a message and a hint for known API errors.
+///
+/// Returns `false` if the error is not expected to happen, in which case it
+/// should be reported with all its details.
+fn print_fetch_error(e: &FetchError) -> bool {
+    match e {
+        FetchError::UnknownParcel(_) => {
+            error!("The parcel is unknown to the carrier.");
+            hint!("Please check the tracking number. A new parcel can take a few hours to appear.");
+        }
+        FetchError::InvalidApiKey(_) => {
+            error!("The API key has been rejected by the carrier.");
+            hint!("You can set a new API key by running `track init -f`.");
+        }
+        FetchError::InvalidFormat(_) => {
+            error!("The tracking number is not valid.");
+            hint!("Please check the tracking number.");
+        }
+        FetchError::RateLimited => {
+            error!("Too many requests have been sent to the API.");
+            hint!("Please wait a bit before trying again.");
+        }
+        FetchError::ServerError(_) => {
+            error!("The API of the carrier is currently unavailable.");
+            hint!("Please try again later.");
+        }
+        FetchError::UnexpectedResponse(_, _) | FetchError::RequestError(_) => {
+            return false
+        }
+    }
+
+    if let Some(message) = e.api_message() {
+        hint!("The API said: {message}");
+    }
+
+    true
+}
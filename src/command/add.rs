@@ -15,9 +15,14 @@
 
 use clap::Parser;
 use eyre::Result;
-use inquire::{required, Text};
+use inquire::{required, validator::Validation, Text};
 
-use crate::{state::State, success};
+use crate::{
+    carrier::CarrierId,
+    state::{Parcel, State},
+    success,
+    tracking_number::{self, ValidationError},
+};
 
 /// Arguments for `track add`.
 #[derive(Debug, Parser)]
@@ -26,13 +31,32 @@ pub struct Add {
     tracking_number: Option<String>,
     /// A description for the parcel.
     description: Option<String>,
+    /// The carrier, identified from the tracking number by default.
+    #[arg(short, long)]
+    carrier: Option<CarrierId>,
+    /// Accept the tracking number as is, without validating its format.
+    #[arg(long)]
+    no_validate: bool,
+    /// Some notes about the parcel.
+    #[arg(short, long)]
+    note: Option<String>,
+    /// A tag for the parcel. Can be used multiple times.
+    #[arg(short, long = "tag", value_name = "TAG")]
+    tags: Vec<String>,
 }
 
 impl super::Command for Add {
-    fn run(&self) -> Result<()> {
+    fn run(&self, _options: &super::GlobalOptions) -> Result<()> {
         let tracking_number = match self.tracking_number.to_owned() {
             Some(value) => value,
-            None => ask_tracking_number()?,
+            None => ask_tracking_number(self.no_validate)?,
+        };
+
+        let tracking_number = if self.no_validate {
+            tracking_number
+        } else {
+            tracking_number::validate(&tracking_number)?;
+            tracking_number::normalise(&tracking_number)
         };
 
         let description = match self.description.to_owned() {
@@ -40,8 +64,19 @@ impl super::Command for Add {
             None => ask_description()?,
         };
 
+        let carrier = self
+            .carrier
+            .or_else(|| CarrierId::identify(&tracking_number))
+            .unwrap_or_default();
+
+        let _lock = State::lock()?;
         let mut state = State::load()?;
-        let old = state.add_parcel(&tracking_number, &description);
+        let parcel = Parcel {
+            notes: self.note.to_owned(),
+            tags: self.tags.to_owned(),
+            ..Parcel::new(description.clone(), carrier)
+        };
+        let old = state.add_parcel(&tracking_number, parcel);
         state.save()?;
 
         match old {
@@ -58,10 +93,29 @@ impl super::Command for Add {
 }
 
 /// Asks for the tracking number.
-fn ask_tracking_number() -> Result<String> {
-    Ok(Text::new("Tracking number:")
-        .with_validator(required!("Please enter a tracking number."))
-        .prompt()?)
+fn ask_tracking_number(no_validate: bool) -> Result<String> {
+    let prompt = Text::new("Tracking number:");
+
+    let prompt = if no_validate {
+        prompt.with_validator(required!("Please enter a tracking number."))
+    } else {
+        prompt.with_validator(validate_tracking_number)
+    };
+
+    Ok(prompt.prompt()?)
+}
+
+/// Validates the tracking number in the prompt.
+fn validate_tracking_number(
+    input: &str,
+) -> Result<Validation, inquire::CustomUserError> {
+    match tracking_number::validate(input) {
+        Ok(_) => Ok(Validation::Valid),
+        Err(ValidationError::Empty) => Ok(Validation::Invalid(
+            "Please enter a tracking number.".into(),
+        )),
+        Err(e) => Ok(Validation::Invalid(e.to_string().into())),
+    }
 }
 
 /// Asks for a description.
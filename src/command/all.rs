@@ -13,37 +13,236 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::{collections::HashMap, num::NonZeroUsize, time::Duration};
+
 use askama::Template;
 use clap::Parser;
-use eyre::{Result, WrapErr};
+use eyre::{bail, Result};
+use thiserror::Error;
 
 use crate::{
-    client::Client, config::Config, state::State,
-    views::tracking_info::TrackingInfo,
+    cache::{Cache, CachedShipment},
+    carrier::{self, ShipmentResults},
+    config::Config,
+    output::{self, TrackingRecord},
+    state::State,
+    success,
+    views::{
+        status_table::StatusTable, tracking_info::TrackingInfo,
+        user_templates::Renderer,
+    },
 };
 
 /// Arguments for `track all`.
 #[derive(Debug, Parser)]
-pub struct All;
+pub struct All {
+    /// The maximum number of concurrent requests.
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+    /// Show the cached tracking info, without accessing the network.
+    #[arg(long, visible_alias = "offline", conflicts_with_all = ["jobs", "max_age"])]
+    cached: bool,
+    /// Only print the parcels with new events since the last run.
+    #[arg(long)]
+    changed: bool,
+    /// Use the cached tracking info when it is not older than this, like `1h`.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    max_age: Option<Duration>,
+    /// Print a single line per parcel, with its latest event.
+    #[arg(short, long)]
+    short: bool,
+}
+
+/// Usage errors of `track all`.
+#[derive(Debug, Error)]
+pub enum AllError {
+    #[error(
+        "{failed} parcel(s) could not be retrieved, {succeeded} succeeded."
+    )]
+    SomeFailed { succeeded: usize, failed: usize },
+    #[error("{0} parcel(s) have no cached tracking info.")]
+    NotCached(usize),
+}
 
 impl super::Command for All {
-    fn run(&self) -> Result<()> {
-        let config = Config::load()?;
+    fn run(&self, options: &super::GlobalOptions) -> Result<()> {
+        // The configuration is only needed to fetch the parcels, so that the
+        // cached tracking info can be shown without it.
+        let config = if self.cached {
+            Config::load().ok()
+        } else {
+            Some(Config::load()?)
+        };
         let state = State::load()?;
-        let client = Client::new(config.api_key())?;
+        let cache = Cache::load()?;
+        // The user template is only needed to print the full tracking info,
+        // so that an invalid one does not break the other formats.
+        let renderer = if options.format.is_machine_readable() || self.short {
+            None
+        } else {
+            Some(Renderer::load()?)
+        };
+        let dates = options.date_formatter(config.as_ref());
+        let max_age = if self.cached {
+            Some(Duration::MAX)
+        } else {
+            self.max_age
+        };
 
-        for (tracking_number, description) in state.parcels() {
-            let events =
-                client.get_events(tracking_number).wrap_err_with(|| format!(
-                    "error getting tracking info for {description} ({tracking_number})"
-                ))?;
+        let parcels = state.sorted_parcels();
 
-            let view =
-                TrackingInfo::new(tracking_number, Some(description), &events)
-                    .render()?;
-            println!("{view}");
+        let mut cached = HashMap::<&str, &CachedShipment>::new();
+        let mut to_fetch = Vec::new();
+        for (tracking_number, parcel) in &parcels {
+            match max_age.and_then(|age| cache.get_fresh(tracking_number, age))
+            {
+                Some(shipment) => {
+                    cached.insert(tracking_number, shipment);
+                }
+                None if self.cached => (),
+                None => {
+                    to_fetch.push((tracking_number.as_str(), parcel.carrier))
+                }
+            }
         }
 
+        let mut results = ShipmentResults::new();
+        match &config {
+            Some(config) if !to_fetch.is_empty() => {
+                let jobs = self.jobs.unwrap_or_else(|| config.jobs());
+                let lang = options.lang(config);
+                results = carrier::get_all(to_fetch, config, lang, jobs)?;
+            }
+            _ => (),
+        }
+
+        // The state and cache may have changed while fetching, so reload them
+        // under lock before updating them.
+        let _state_lock = State::lock()?;
+        let _cache_lock = Cache::lock()?;
+        let mut fresh_state = State::load()?;
+        let mut fresh_cache = Cache::load()?;
+
+        let mut failed = 0;
+        let mut not_cached = 0;
+        let mut changed = 0;
+        let mut records = Vec::new();
+        let mut table = StatusTable::default();
+
+        for (tracking_number, parcel) in &parcels {
+            let description = Some(parcel.description.as_str());
+
+            let (result, fetched_at) =
+                match cached.get(tracking_number.as_str()) {
+                    Some(cached) => {
+                        (Ok(cached.shipment.clone()), Some(cached.fetched_at))
+                    }
+                    None => match results.remove(tracking_number.as_str()) {
+                        Some(result) => {
+                            if let Ok(shipment) = &result {
+                                fresh_cache
+                                    .insert(tracking_number, shipment.clone());
+                            }
+                            (result, None)
+                        }
+                        None => {
+                            not_cached += 1;
+                            continue;
+                        }
+                    },
+                };
+
+            match &result {
+                Ok(shipment) => {
+                    fresh_state.update_status(tracking_number, shipment);
+                }
+                Err(_) => failed += 1,
+            }
+
+            let mut view = TrackingInfo::new(
+                tracking_number,
+                description,
+                result.as_ref(),
+            )
+            .highlight_new_since(parcel.last_seen_event)
+            .format_dates(dates.clone());
+
+            if let Some(fetched_at) = fetched_at {
+                view = view.cached(fetched_at);
+            }
+
+            if view.has_new_events() {
+                changed += 1;
+            } else if self.changed && result.is_ok() {
+                continue;
+            }
+
+            if options.format.is_machine_readable() {
+                let record = TrackingRecord::new(
+                    tracking_number,
+                    description,
+                    result.as_ref(),
+                );
+                records.push(record);
+            } else if self.short {
+                let description = &parcel.description;
+                table.push(tracking_number, description, result.as_ref());
+            } else if let Some(renderer) = &renderer {
+                println!("{}", renderer.render(&view)?);
+            }
+        }
+
+        if options.format.is_machine_readable() {
+            output::print_tracking_info(options.format, &records)?;
+        } else if !table.is_empty() {
+            println!("{}", table.render()?);
+        }
+
+        // Without a configuration, the grace period is unknown: the delivered
+        // parcels are archived on the next run with one.
+        let archived = match &config {
+            Some(config) => {
+                fresh_state.archive_delivered(config.archive_after())
+            }
+            None => Vec::new(),
+        };
+
+        fresh_state.save()?;
+        fresh_cache.save()?;
+
+        if !options.format.is_machine_readable() {
+            for (tracking_number, parcel) in archived {
+                let description = parcel.description;
+                success!(
+                    "{description} ({tracking_number}) has been archived."
+                );
+            }
+        }
+
+        if not_cached > 0 {
+            bail!(AllError::NotCached(not_cached));
+        }
+
+        if failed > 0 {
+            let succeeded = parcels.len() - failed;
+            bail!(AllError::SomeFailed { succeeded, failed });
+        }
+
+        if options.format.is_machine_readable() {
+            return Ok(());
+        }
+
+        if self.changed {
+            success!(
+                "Tracking info retrieved for {} parcel(s), {changed} with new events.",
+                parcels.len()
+            );
+        } else {
+            success!(
+                "Tracking info retrieved for {} parcel(s).",
+                parcels.len()
+            );
+        }
         Ok(())
     }
 }
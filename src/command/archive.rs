@@ -0,0 +1,100 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use eyre::Result;
+use thiserror::Error;
+
+use crate::{dates::DateFormatter, state::State, success};
+
+/// Arguments for `track archive`.
+#[derive(Debug, Parser)]
+pub struct Archive {
+    #[command(subcommand)]
+    command: ArchiveCommand,
+}
+
+/// The subcommands of `track archive`.
+#[derive(Debug, Subcommand)]
+enum ArchiveCommand {
+    /// Print the archived parcels.
+    List,
+    /// Restore an archived parcel to the tracked set.
+    Restore {
+        /// The tracking number.
+        tracking_number: String,
+    },
+}
+
+/// Usage errors of `track archive`.
+#[derive(Debug, Error)]
+pub enum ArchiveError {
+    #[error("{0} is not archived.")]
+    NotArchived(String),
+}
+
+impl super::Command for Archive {
+    fn run(&self, options: &super::GlobalOptions) -> Result<()> {
+        match &self.command {
+            ArchiveCommand::List => list(&options.offline_date_formatter()),
+            ArchiveCommand::Restore { tracking_number } => {
+                restore(tracking_number)
+            }
+        }
+    }
+}
+
+/// Prints the archived parcels.
+fn list(dates: &DateFormatter) -> Result<()> {
+    let state = State::load()?;
+
+    println!("\n{}\n", "--- Archived parcels ---".bold());
+    for (tracking_number, parcel) in state.sorted_archive() {
+        let description = &parcel.description;
+
+        match parcel.delivered_at {
+            Some(date) => {
+                let delivered =
+                    format!("(delivered on {})", dates.format(date));
+                println!(
+                    "{tracking_number}: {description} {}",
+                    delivered.bright_black()
+                );
+            }
+            None => println!("{tracking_number}: {description}"),
+        }
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Restores an archived parcel.
+fn restore(tracking_number: &str) -> Result<()> {
+    let _lock = State::lock()?;
+    let mut state = State::load()?;
+
+    let description = state
+        .restore_parcel(tracking_number)
+        .ok_or_else(|| ArchiveError::NotArchived(tracking_number.to_owned()))?
+        .description
+        .clone();
+
+    state.save()?;
+
+    success!("{description} ({tracking_number}) is tracked again.");
+    Ok(())
+}
@@ -0,0 +1,110 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Colour policy.
+
+use std::{
+    env,
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::ValueEnum;
+use color_eyre::config::{HookBuilder, Theme};
+use eyre::Result;
+
+/// When to use colours.
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Use colours when printing to a terminal.
+    #[default]
+    Auto,
+    /// Always use colours.
+    Always,
+    /// Never use colours.
+    Never,
+}
+
+/// Whether to use colours on the standard output.
+static STDOUT_COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Whether to use colours on the standard error.
+static STDERR_COLOR: AtomicBool = AtomicBool::new(false);
+
+impl ColorChoice {
+    /// Applies the colour policy to all outputs, including error reports.
+    ///
+    /// The policy is decided separately for the standard output and error.
+    pub fn apply(self) -> Result<()> {
+        let stdout_color = self.should_colorize(io::stdout().is_terminal());
+        let stderr_color = self.should_colorize(io::stderr().is_terminal());
+
+        STDOUT_COLOR.store(stdout_color, Ordering::Relaxed);
+        STDERR_COLOR.store(stderr_color, Ordering::Relaxed);
+        colored::control::set_override(stdout_color);
+
+        let theme = if stderr_color {
+            Theme::dark()
+        } else {
+            Theme::new()
+        };
+        HookBuilder::default().theme(theme).install()?;
+
+        Ok(())
+    }
+
+    /// Returns whether to use colours on a stream.
+    ///
+    /// In `auto` mode, a non-empty `NO_COLOR` disables colours, then a
+    /// `CLICOLOR_FORCE` other than `0` enables them. Otherwise, colours are used
+    /// only when the stream is a terminal.
+    fn should_colorize(self, is_terminal: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                if is_set("NO_COLOR") {
+                    false
+                } else if is_forced("CLICOLOR_FORCE") {
+                    true
+                } else {
+                    is_terminal
+                }
+            }
+        }
+    }
+}
+
+/// Runs `f` with the colour policy of the standard error.
+///
+/// This is meant for printing to the standard error, as `colored` only supports
+/// a global policy.
+pub fn on_stderr<T>(f: impl FnOnce() -> T) -> T {
+    colored::control::set_override(STDERR_COLOR.load(Ordering::Relaxed));
+    let result = f();
+    colored::control::set_override(STDOUT_COLOR.load(Ordering::Relaxed));
+    result
+}
+
+/// Returns whether an environment variable is set to a non-empty value.
+fn is_set(var: &str) -> bool {
+    env::var_os(var).is_some_and(|value| !value.is_empty())
+}
+
+/// Returns whether an environment variable is set to a non-empty value other
+/// than `0`.
+fn is_forced(var: &str) -> bool {
+    env::var_os(var).is_some_and(|value| !value.is_empty() && value != "0")
+}
@@ -23,22 +23,26 @@ macro_rules! success {
     }};
 }
 
-/// Prints an error.
+/// Prints an error on the standard error.
 #[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => {{
         use colored::Colorize;
-        let message = format!($($arg)*).red().bold();
-        println!("{message}");
+        $crate::command::color::on_stderr(|| {
+            let message = format!($($arg)*).red().bold();
+            eprintln!("{message}");
+        });
     }};
 }
 
-/// Prints a hint.
+/// Prints a hint on the standard error.
 #[macro_export]
 macro_rules! hint {
     ($($arg:tt)*) => {{
         use colored::Colorize;
-        let message = format!($($arg)*).blue();
-        println!("{message}");
+        $crate::command::color::on_stderr(|| {
+            let message = format!($($arg)*).blue();
+            eprintln!("{message}");
+        });
     }};
 }
@@ -13,12 +13,19 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use askama::Template;
+use chrono::{DateTime, Local};
 use clap::Parser;
 use eyre::{Result, WrapErr};
+use thiserror::Error;
 
 use crate::{
-    client::Client, config::Config, views::tracking_info::TrackingInfo,
+    cache::Cache,
+    carrier::{CarrierId, Shipment},
+    config::Config,
+    dates::DateFormatter,
+    output::{self, Format, TrackingRecord},
+    state::State,
+    views::{tracking_info::TrackingInfo, user_templates::Renderer},
 };
 
 /// Arguments for `track info`.
@@ -26,23 +33,112 @@ use crate::{
 pub struct Info {
     /// The tracking number.
     tracking_number: String,
+    /// The carrier, identified from the tracking number by default.
+    #[arg(short, long)]
+    carrier: Option<CarrierId>,
+    /// Show the cached tracking info, without accessing the network.
+    #[arg(long, visible_alias = "offline", conflicts_with = "carrier")]
+    cached: bool,
+}
+
+/// Usage errors of `track info`.
+#[derive(Debug, Error)]
+pub enum InfoError {
+    #[error("There is no cached tracking info for {0}.")]
+    NotCached(String),
 }
 
 impl super::Command for Info {
-    fn run(&self) -> Result<()> {
-        let Self { tracking_number } = self;
+    fn run(&self, options: &super::GlobalOptions) -> Result<()> {
+        let Self {
+            tracking_number,
+            carrier,
+            cached,
+        } = self;
+
+        if *cached {
+            let dates = options.offline_date_formatter();
+            return show_cached(tracking_number, options.format, dates);
+        }
+
         let config = Config::load()?;
-        let client = Client::new(config.api_key())?;
+        let dates = options.date_formatter(Some(&config));
+
+        let carrier = match carrier {
+            Some(carrier) => *carrier,
+            None => identify_carrier(tracking_number)?,
+        };
+        let client = carrier.client(&config, options.lang(&config))?;
 
-        let events =
-            client.get_events(tracking_number).wrap_err_with(|| {
+        let shipment =
+            client.get_shipment(tracking_number).wrap_err_with(|| {
                 format!("error getting tracking info for {tracking_number}")
             })?;
 
-        let view =
-            TrackingInfo::new(tracking_number, None, &events).render()?;
-        println!("{view}");
+        let _lock = Cache::lock()?;
+        let mut cache = Cache::load()?;
+        cache.insert(tracking_number, shipment.clone());
+        cache.save()?;
+
+        print(tracking_number, &shipment, None, options.format, dates)
+    }
+}
+
+/// Shows the cached tracking info of a parcel.
+fn show_cached(
+    tracking_number: &str,
+    format: Format,
+    dates: DateFormatter,
+) -> Result<()> {
+    let cache = Cache::load()?;
+    let cached = cache
+        .get(tracking_number)
+        .ok_or_else(|| InfoError::NotCached(tracking_number.to_owned()))?;
 
-        Ok(())
+    print(
+        tracking_number,
+        &cached.shipment,
+        Some(cached.fetched_at),
+        format,
+        dates,
+    )
+}
+
+/// Prints the tracking info of a parcel in the given format.
+fn print(
+    tracking_number: &str,
+    shipment: &Shipment,
+    fetched_at: Option<DateTime<Local>>,
+    format: Format,
+    dates: DateFormatter,
+) -> Result<()> {
+    if format.is_machine_readable() {
+        let record = TrackingRecord::new(tracking_number, None, Ok(shipment));
+        output::print_tracking_info(format, &[record])?;
+        return Ok(());
     }
+
+    let mut view = TrackingInfo::new(tracking_number, None, Ok(shipment))
+        .format_dates(dates);
+    if let Some(fetched_at) = fetched_at {
+        view = view.cached(fetched_at);
+    }
+
+    let renderer = Renderer::load()?;
+    println!("{}", renderer.render(&view)?);
+    Ok(())
+}
+
+/// Identifies the carrier of a parcel.
+///
+/// The carrier stored in the state is used for tracked parcels.
+fn identify_carrier(tracking_number: &str) -> Result<CarrierId> {
+    let state = State::load()?;
+
+    let carrier = match state.parcel(tracking_number) {
+        Some(parcel) => parcel.carrier,
+        None => CarrierId::identify(tracking_number).unwrap_or_default(),
+    };
+
+    Ok(carrier)
 }
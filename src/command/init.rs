@@ -18,7 +18,10 @@ use eyre::{bail, Result};
 use inquire::Text;
 use thiserror::Error;
 
-use crate::{config::Config, success};
+use crate::{
+    config::{Config, LoadError},
+    success,
+};
 
 /// Arguments for `track init`.
 #[derive(Debug, Parser)]
@@ -36,13 +39,27 @@ pub enum InitError {
 }
 
 impl super::Command for Init {
-    fn run(&self) -> Result<()> {
-        if !self.force && Config::load().is_ok() {
+    fn run(&self, _options: &super::GlobalOptions) -> Result<()> {
+        let config = Config::load();
+        let existing_config =
+            matches!(config, Ok(_) | Err(LoadError::NewerVersion { .. }));
+
+        if !self.force && existing_config {
             bail!(InitError::ExistingConfig);
         }
 
         let api_key = Text::new("La Poste API key:").prompt()?;
-        Config::new(api_key).save()?;
+
+        // Only replace the API key of a valid configuration, to keep the
+        // other settings when re-initialising with `--force`.
+        let config = match config {
+            Ok(mut config) => {
+                config.set_api_key(api_key);
+                config
+            }
+            Err(_) => Config::new(api_key),
+        };
+        config.save()?;
 
         success!("The configuration has been initialised.");
         Ok(())
@@ -17,19 +17,42 @@ use clap::Parser;
 use colored::Colorize;
 use eyre::Result;
 
-use crate::state::State;
+use crate::{
+    output::{self, ParcelRecord},
+    state::State,
+};
 
 /// Arguments for `track list`.
 #[derive(Debug, Parser)]
 pub struct List;
 
 impl super::Command for List {
-    fn run(&self) -> Result<()> {
+    fn run(&self, options: &super::GlobalOptions) -> Result<()> {
         let state = State::load()?;
+        let parcels = state.sorted_parcels();
+
+        if options.format.is_machine_readable() {
+            let records = parcels
+                .into_iter()
+                .map(|(number, parcel)| ParcelRecord::new(number, parcel))
+                .collect::<Vec<_>>();
+            output::print_parcels(options.format, &records)?;
+            return Ok(());
+        }
 
         println!("\n{}\n", "--- Tracked parcels ---".bold());
-        for (tracking_number, description) in state.parcels() {
-            println!("{tracking_number}: {description}");
+        for (tracking_number, parcel) in parcels {
+            let description = &parcel.description;
+
+            if parcel.tags.is_empty() {
+                println!("{tracking_number}: {description}");
+            } else {
+                let tags = format!("[{}]", parcel.tags.join(", "));
+                println!(
+                    "{tracking_number}: {description} {}",
+                    tags.bright_black()
+                );
+            }
         }
         println!();
 
@@ -0,0 +1,51 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::time::Duration;
+
+use clap::Parser;
+use eyre::Result;
+
+use crate::{cache::Cache, state::State, success};
+
+/// Arguments for `track prune`.
+#[derive(Debug, Parser)]
+pub struct Prune {
+    /// Only prune the parcels delivered for longer than this, like `30d`.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    older_than: Option<Duration>,
+}
+
+impl super::Command for Prune {
+    fn run(&self, _options: &super::GlobalOptions) -> Result<()> {
+        let _state_lock = State::lock()?;
+        let _cache_lock = Cache::lock()?;
+        let mut state = State::load()?;
+        let mut cache = Cache::load()?;
+
+        let pruned =
+            state.prune_archive(self.older_than.unwrap_or(Duration::ZERO));
+
+        for (tracking_number, _) in &pruned {
+            cache.remove(tracking_number);
+        }
+
+        state.save()?;
+        cache.save()?;
+
+        success!("{} archived parcel(s) pruned.", pruned.len());
+        Ok(())
+    }
+}
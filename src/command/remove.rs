@@ -19,7 +19,11 @@ use inquire::Select;
 use regex::Regex;
 use thiserror::Error;
 
-use crate::{state::State, success};
+use crate::{
+    cache::Cache,
+    state::{Parcel, State},
+    success,
+};
 
 /// Arguments for `track remove`.
 #[derive(Debug, Parser)]
@@ -38,20 +42,28 @@ pub enum RemoveError {
 }
 
 impl super::Command for Remove {
-    fn run(&self) -> Result<()> {
-        let mut state = State::load()?;
-
+    fn run(&self, _options: &super::GlobalOptions) -> Result<()> {
         let tracking_number = match self.tracking_number.to_owned() {
             Some(value) => value,
-            None => ask_parcel(&state)?,
+            None => ask_parcel(&State::load()?)?,
         };
 
-        let description = state
+        let _lock = State::lock()?;
+        let mut state = State::load()?;
+
+        let parcel = state
             .remove_parcel(&tracking_number)
             .ok_or_else(|| RemoveError::NotTracked(tracking_number.clone()))?;
 
         state.save()?;
 
+        let _cache_lock = Cache::lock()?;
+        let mut cache = Cache::load()?;
+        if cache.remove(&tracking_number).is_some() {
+            cache.save()?;
+        }
+
+        let description = parcel.description;
         success!("{description} ({tracking_number}) is not tracked anymore.");
         Ok(())
     }
@@ -73,9 +85,9 @@ fn ask_parcel(state: &State) -> Result<String> {
 }
 
 /// Builds an option from a parcel tuple.
-fn to_option(parcel: (&String, &String)) -> String {
-    let (tracking_number, description) = parcel;
-    format!("{tracking_number}: {description}")
+fn to_option(parcel: (&String, &Parcel)) -> String {
+    let (tracking_number, parcel) = parcel;
+    format!("{tracking_number}: {}", parcel.description)
 }
 
 /// Extracts the tracking number from a selected option.
@@ -0,0 +1,77 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fs;
+
+use clap::{Parser, Subcommand};
+use eyre::{bail, Result};
+use thiserror::Error;
+
+use crate::{
+    file, success,
+    views::user_templates::{self, DEFAULT_TRACKING_INFO, TRACKING_INFO},
+};
+
+/// Arguments for `track template`.
+#[derive(Debug, Parser)]
+pub struct Template {
+    #[command(subcommand)]
+    command: TemplateCommand,
+}
+
+/// The subcommands of `track template`.
+#[derive(Debug, Subcommand)]
+enum TemplateCommand {
+    /// Write the default templates to the configuration directory.
+    Dump {
+        /// Overwrite the existing templates.
+        #[arg(short, long)]
+        force: bool,
+    },
+}
+
+/// Usage errors of `track template`.
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("There is already a template at {0}.")]
+    ExistingTemplate(String),
+}
+
+impl super::Command for Template {
+    fn run(&self, _options: &super::GlobalOptions) -> Result<()> {
+        match &self.command {
+            TemplateCommand::Dump { force } => dump(*force),
+        }
+    }
+}
+
+/// Writes the default templates to the configuration directory.
+fn dump(force: bool) -> Result<()> {
+    let templates_dir = user_templates::templates_dir()?;
+    let path = templates_dir.join(TRACKING_INFO);
+
+    if !force && path.exists() {
+        bail!(TemplateError::ExistingTemplate(path.display().to_string()));
+    }
+
+    fs::create_dir_all(&templates_dir)?;
+    file::write_atomic(&path, DEFAULT_TRACKING_INFO, None)?;
+
+    success!(
+        "The default template has been written to {}.",
+        path.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,278 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::{num::NonZeroUsize, process, thread, time::Duration};
+
+use clap::Parser;
+use colored::Colorize;
+use eyre::Result;
+
+use crate::{
+    cache::Cache,
+    carrier::{self, Event, Lang},
+    config::Config,
+    dates::DateFormatter,
+    error,
+    notifier::Notifier,
+    state::{Parcel, State},
+    success,
+};
+
+/// Arguments for `track watch`.
+#[derive(Debug, Parser)]
+pub struct Watch {
+    /// The interval between two checks, like `30m`.
+    #[arg(short, long, value_parser = humantime::parse_duration)]
+    interval: Option<Duration>,
+    /// The maximum number of concurrent requests.
+    #[arg(short, long)]
+    jobs: Option<NonZeroUsize>,
+    /// Send a desktop notification for new events.
+    #[arg(short, long)]
+    notify: bool,
+}
+
+impl super::Command for Watch {
+    fn run(&self, options: &super::GlobalOptions) -> Result<()> {
+        let config = Config::load()?;
+        let interval = self.interval.unwrap_or_else(|| config.watch_interval());
+        let jobs = self.jobs.unwrap_or_else(|| config.jobs());
+        let lang = options.lang(&config);
+        let dates = options.date_formatter(Some(&config));
+
+        let notifier = if self.notify || config.notify() {
+            Some(Notifier::new()?)
+        } else {
+            None
+        };
+
+        loop {
+            check(&config, lang, jobs, &dates, notifier.as_ref())?;
+
+            let message = format!(
+                "Next check in {}.",
+                humantime::format_duration(interval)
+            );
+            println!("{}", message.bright_black());
+
+            thread::sleep(interval);
+        }
+    }
+}
+
+/// The new events of a parcel, found during a check.
+#[derive(Debug)]
+struct Update<'a> {
+    tracking_number: &'a str,
+    parcel: &'a Parcel,
+    new_events: Vec<Event>,
+    delivered: bool,
+}
+
+/// Checks the tracked parcels for new events.
+///
+/// Delivered parcels are not checked anymore. Parcels checked for the first
+/// time report the events since they have been added, or only record their
+/// last event if the date of addition is unknown.
+fn check(
+    config: &Config,
+    lang: Option<Lang>,
+    jobs: NonZeroUsize,
+    dates: &DateFormatter,
+    notifier: Option<&Notifier>,
+) -> Result<()> {
+    let state = State::load()?;
+    let parcels = state
+        .sorted_parcels()
+        .into_iter()
+        .filter(|(_, parcel)| !parcel.delivered)
+        .collect::<Vec<_>>();
+
+    let mut results = carrier::get_all(
+        parcels
+            .iter()
+            .map(|(number, parcel)| (number.as_str(), parcel.carrier)),
+        config,
+        lang,
+        jobs,
+    )?;
+
+    let mut updates = Vec::new();
+
+    // The hooks may run `track` themselves, so they must run without the locks.
+    let archived = {
+        let _state_lock = State::lock()?;
+        let _cache_lock = Cache::lock()?;
+        let mut fresh_state = State::load()?;
+        let mut fresh_cache = Cache::load()?;
+
+        for (tracking_number, parcel) in &parcels {
+            let result = results
+                .remove(tracking_number.as_str())
+                .expect("a result is returned for each parcel");
+
+            let shipment = match result {
+                Ok(shipment) => shipment,
+                Err(e) => {
+                    let description = &parcel.description;
+                    error!("{description} ({tracking_number}): {e}");
+                    continue;
+                }
+            };
+
+            // Parcels without any event seen yet start from their addition.
+            // Only the ones migrated from the old state, with no date of
+            // addition, are silently seeded.
+            if let Some(baseline) = parcel.last_seen_event.or(parcel.added_at) {
+                let mut new_events = shipment
+                    .events
+                    .iter()
+                    .filter(|event| event.date > baseline)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                new_events.sort_by_key(|event| event.date);
+
+                updates.push(Update {
+                    tracking_number,
+                    parcel,
+                    new_events,
+                    delivered: shipment.delivered && !parcel.delivered,
+                });
+            }
+
+            fresh_state.update_status(tracking_number, &shipment);
+            fresh_cache.insert(tracking_number, shipment);
+        }
+
+        let archived = fresh_state.archive_delivered(config.archive_after());
+
+        fresh_state.save()?;
+        fresh_cache.save()?;
+
+        archived
+    };
+
+    for update in &updates {
+        let Update {
+            tracking_number,
+            parcel,
+            new_events,
+            delivered,
+        } = update;
+
+        for event in new_events {
+            print_event(tracking_number, parcel, event, dates);
+            run_hooks(config.hooks(), tracking_number, parcel, event);
+        }
+
+        if let Some(notifier) = notifier {
+            notify(notifier, tracking_number, parcel, new_events, *delivered);
+        }
+    }
+
+    for (tracking_number, parcel) in archived {
+        let description = parcel.description;
+        success!("{description} ({tracking_number}) has been archived.");
+    }
+
+    Ok(())
+}
+
+/// Prints a new event.
+fn print_event(
+    tracking_number: &str,
+    parcel: &Parcel,
+    event: &Event,
+    dates: &DateFormatter,
+) {
+    let date = format!("{}:", dates.format(event.date));
+    let parcel = format!("{} ({tracking_number})", parcel.description);
+    println!("{} {} {}", date.bright_black(), parcel.bold(), event.label);
+}
+
+/// Sends a notification for the new events of a parcel.
+fn notify(
+    notifier: &Notifier,
+    tracking_number: &str,
+    parcel: &Parcel,
+    new_events: &[Event],
+    delivered: bool,
+) {
+    if new_events.is_empty() && !delivered {
+        return;
+    }
+
+    let description = &parcel.description;
+    let summary = if delivered {
+        format!("{description} has been delivered")
+    } else {
+        format!("{description} ({tracking_number})")
+    };
+
+    let body = new_events
+        .iter()
+        .map(|event| event.label.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if let Err(e) = notifier.notify(&summary, &body) {
+        error!("The notification for {description} could not be sent: {e}.");
+    }
+}
+
+/// Runs the hooks for a new event.
+///
+/// The event is passed to the hooks through environment variables.
+fn run_hooks(
+    hooks: &[String],
+    tracking_number: &str,
+    parcel: &Parcel,
+    event: &Event,
+) {
+    for hook in hooks {
+        let status = shell(hook)
+            .env("TRACK_TRACKING_NUMBER", tracking_number)
+            .env("TRACK_DESCRIPTION", &parcel.description)
+            .env("TRACK_EVENT_DATE", event.date.to_rfc3339())
+            .env("TRACK_EVENT_LABEL", &event.label)
+            .env(
+                "TRACK_EVENT_CODE",
+                event.code.as_deref().unwrap_or_default(),
+            )
+            .status();
+
+        match status {
+            Ok(status) if status.success() => (),
+            Ok(status) => error!("The hook `{hook}` has failed ({status})."),
+            Err(e) => error!("The hook `{hook}` could not be run: {e}."),
+        }
+    }
+}
+
+/// Builds a command running a hook in the shell.
+#[cfg(unix)]
+fn shell(hook: &str) -> process::Command {
+    let mut command = process::Command::new("sh");
+    command.arg("-c").arg(hook);
+    command
+}
+
+/// Builds a command running a hook in the shell.
+#[cfg(windows)]
+fn shell(hook: &str) -> process::Command {
+    let mut command = process::Command::new("cmd");
+    command.arg("/C").arg(hook);
+    command
+}
@@ -13,16 +13,98 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{fs, io, path::PathBuf};
+use std::{
+    env, fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+use chrono_tz::Tz;
 use derive_new::new;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use toml::Table;
+
+use crate::{
+    carrier::Lang,
+    dates::DateFormat,
+    file,
+    versioned::{self, Migration, UpgradeError},
+};
+
 /// The configuration for `track`.
 #[derive(new, Debug, Serialize, Deserialize)]
 pub struct Config {
+    #[new(value = "versioned::current_version(MIGRATIONS)")]
+    version: u32,
     api_key: String,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jobs: Option<NonZeroUsize>,
+    #[new(default)]
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    connect_timeout: Option<Duration>,
+    #[new(default)]
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    timeout: Option<Duration>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_retries: Option<u32>,
+    #[new(default)]
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    retry_delay: Option<Duration>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_base_url: Option<String>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proxy: Option<String>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extra_ca_certs: Option<PathBuf>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lang: Option<Lang>,
+    #[new(default)]
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    watch_interval: Option<Duration>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hooks: Option<Vec<String>>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notify: Option<bool>,
+    #[new(default)]
+    #[serde(
+        default,
+        with = "humantime_serde",
+        skip_serializing_if = "Option::is_none"
+    )]
+    archive_after: Option<Duration>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date_format: Option<DateFormat>,
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timezone: Option<Tz>,
 }
 
 /// An error that can occur when loading the config.
@@ -33,7 +115,18 @@ pub enum LoadError {
     #[error("error while reading the config file")]
     ReadError(#[from] io::Error),
     #[error("error while parsing the config file")]
-    ParseError(#[from] toml::de::Error),
+    ParseError(#[source] toml::de::Error),
+    #[error("invalid version in the config file")]
+    InvalidVersion,
+    #[error(
+        "the config file is from a newer version of track (version {found}, \
+         supported up to {supported})"
+    )]
+    NewerVersion { found: u32, supported: u32 },
+    #[error("error while backing up the config file")]
+    BackupError(#[source] io::Error),
+    #[error("error while saving the migrated config file")]
+    SaveError(#[from] SaveError),
 }
 
 /// An error that can occur when saving the config.
@@ -52,16 +145,56 @@ pub enum ConfigDirError {
     NoConfigDir,
 }
 
+/// The default base URL of the La Poste “Suivi v2” API.
+const DEFAULT_API_BASE_URL: &str = "https://api.laposte.fr/suivi/v2";
+
+/// The environment variable overriding the API base URL.
+const API_BASE_URL_VAR: &str = "TRACK_API_URL";
+
+/// The default number of concurrent requests.
+const DEFAULT_JOBS: NonZeroUsize = NonZeroUsize::new(4).expect("not zero");
+
+/// The default timeout for connecting to the API.
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The default timeout for a whole request to the API.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The default number of retries on transient errors.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// The default delay before the first retry.
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The default grace period before archiving delivered parcels.
+const DEFAULT_ARCHIVE_AFTER: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// The default interval between two checks in `track watch`.
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 /// The configuration file name.
 const CONFIG_FILE_NAME: &str = "config.toml";
 
+/// The permissions of the configuration file, which contains the API key.
+const CONFIG_FILE_MODE: u32 = 0o600;
+
+/// The migrations of the configuration file, from version 0.
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1];
+
 impl Config {
     /// Loads the configuration.
     pub fn load() -> Result<Self, LoadError> {
         let config_file = config_file()?;
-        let contents = fs::read_to_string(config_file)?;
-        let config = toml::from_str(&contents)?;
-        Ok(config)
+        let contents = fs::read_to_string(&config_file)?;
+        let upgraded = versioned::upgrade::<Self>(&contents, MIGRATIONS)?;
+
+        if upgraded.migrated_from.is_some() {
+            versioned::backup(&config_file, &contents, Some(CONFIG_FILE_MODE))
+                .map_err(LoadError::BackupError)?;
+            upgraded.value.save()?;
+        }
+
+        Ok(upgraded.value)
     }
 
     /// Saves the configuration.
@@ -71,7 +204,7 @@ impl Config {
         let config_file = config_file()?;
         let config =
             toml::to_string(self).expect("failed to serialise the config");
-        fs::write(config_file, config)?;
+        file::write_atomic(&config_file, &config, Some(CONFIG_FILE_MODE))?;
 
         Ok(())
     }
@@ -80,10 +213,115 @@ impl Config {
     pub fn api_key(&self) -> &str {
         &self.api_key
     }
+
+    /// Sets the API key, keeping the other settings.
+    pub fn set_api_key(&mut self, api_key: String) {
+        self.api_key = api_key;
+    }
+
+    /// Gets the base URL of the API.
+    ///
+    /// The `TRACK_API_URL` environment variable takes precedence over the
+    /// configuration file.
+    pub fn api_base_url(&self) -> String {
+        env::var(API_BASE_URL_VAR)
+            .ok()
+            .or_else(|| self.api_base_url.clone())
+            .unwrap_or_else(|| DEFAULT_API_BASE_URL.to_owned())
+    }
+
+    /// Gets the proxy to use for all requests, if any.
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// Gets the path to a PEM file with additional CA certificates, if any.
+    pub fn extra_ca_certs(&self) -> Option<&Path> {
+        self.extra_ca_certs.as_deref()
+    }
+
+    /// Gets the language of the tracking info, if set.
+    pub fn lang(&self) -> Option<Lang> {
+        self.lang
+    }
+
+    /// Gets the maximum number of concurrent requests.
+    pub fn jobs(&self) -> NonZeroUsize {
+        self.jobs.unwrap_or(DEFAULT_JOBS)
+    }
+
+    /// Gets the timeout for connecting to the API.
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT)
+    }
+
+    /// Gets the timeout for a whole request to the API.
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or(DEFAULT_TIMEOUT)
+    }
+
+    /// Gets the number of retries on transient errors.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES)
+    }
+
+    /// Gets the delay before the first retry.
+    ///
+    /// The delay is doubled after each retry.
+    pub fn retry_delay(&self) -> Duration {
+        self.retry_delay.unwrap_or(DEFAULT_RETRY_DELAY)
+    }
+
+    /// Gets the grace period before archiving delivered parcels.
+    pub fn archive_after(&self) -> Duration {
+        self.archive_after.unwrap_or(DEFAULT_ARCHIVE_AFTER)
+    }
+
+    /// Gets the interval between two checks in `track watch`.
+    pub fn watch_interval(&self) -> Duration {
+        self.watch_interval.unwrap_or(DEFAULT_WATCH_INTERVAL)
+    }
+
+    /// Gets whether `track watch` sends desktop notifications.
+    pub fn notify(&self) -> bool {
+        self.notify.unwrap_or_default()
+    }
+
+    /// Gets the format of the dates in the tracking info, if set.
+    pub fn date_format(&self) -> Option<&DateFormat> {
+        self.date_format.as_ref()
+    }
+
+    /// Gets the timezone in which to print dates, if set.
+    pub fn timezone(&self) -> Option<Tz> {
+        self.timezone
+    }
+
+    /// Gets the commands to run when a parcel has new events.
+    pub fn hooks(&self) -> &[String] {
+        self.hooks.as_deref().unwrap_or_default()
+    }
+}
+
+impl From<UpgradeError> for LoadError {
+    fn from(error: UpgradeError) -> Self {
+        match error {
+            UpgradeError::ParseError(e) => Self::ParseError(e),
+            UpgradeError::InvalidVersion => Self::InvalidVersion,
+            UpgradeError::NewerVersion { found, supported } => {
+                Self::NewerVersion { found, supported }
+            }
+        }
+    }
 }
 
+/// Migrates the configuration from version 0 to version 1.
+///
+/// Version 1 only adds the `version` field.
+fn migrate_v0_to_v1(_config: &mut Table) {}
+
 /// Gets the config directory for `track`.
-fn config_dir() -> Result<PathBuf, ConfigDirError> {
+pub fn config_dir() -> Result<PathBuf, ConfigDirError> {
     Ok(dirs::config_dir()
         .ok_or(ConfigDirError::NoConfigDir)?
         .join(env!("CARGO_PKG_NAME")))
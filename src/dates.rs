@@ -0,0 +1,183 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Date formatting.
+
+use std::{fmt, str::FromStr};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike, Local, TimeZone,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// A date format.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum DateFormat {
+    /// RFC 2822, like `Tue, 30 May 2023 08:00:00 +0200`.
+    #[default]
+    Rfc2822,
+    /// Relative to now, like `3 h ago` or `yesterday 14:02`.
+    Relative,
+    /// ISO 8601, like `2023-05-30T08:00:00+02:00`.
+    Iso8601,
+    /// A custom `strftime` pattern, like `%d/%m %H:%M`.
+    Custom(String),
+}
+
+/// A date formatter.
+#[derive(Debug, Clone, Default)]
+pub struct DateFormatter {
+    format: DateFormat,
+    timezone: Option<Tz>,
+}
+
+/// An error that can occur when parsing a date format.
+#[derive(Debug, Error)]
+#[error(
+    "invalid date format `{0}`: expected `rfc2822`, `relative`, `iso8601` or \
+     a strftime pattern"
+)]
+pub struct ParseDateFormatError(String);
+
+/// The format of times in relative dates.
+const TIME_FORMAT: &str = "%H:%M";
+
+/// The format of dates of the current week in relative dates.
+const WEEKDAY_FORMAT: &str = "%a %H:%M";
+
+/// The format of older dates in relative dates.
+const FULL_FORMAT: &str = "%-d %b %Y %H:%M";
+
+/// The number of days for which relative dates show the day of the week.
+const WEEKDAY_DAYS: i64 = 6;
+
+impl FromStr for DateFormat {
+    type Err = ParseDateFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rfc2822" => Ok(Self::Rfc2822),
+            "relative" => Ok(Self::Relative),
+            "iso8601" => Ok(Self::Iso8601),
+            pattern if is_valid_pattern(pattern) => {
+                Ok(Self::Custom(pattern.to_owned()))
+            }
+            _ => Err(ParseDateFormatError(s.to_owned())),
+        }
+    }
+}
+
+impl TryFrom<String> for DateFormat {
+    type Error = ParseDateFormatError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<DateFormat> for String {
+    fn from(format: DateFormat) -> Self {
+        format.to_string()
+    }
+}
+
+impl fmt::Display for DateFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rfc2822 => write!(f, "rfc2822"),
+            Self::Relative => write!(f, "relative"),
+            Self::Iso8601 => write!(f, "iso8601"),
+            Self::Custom(pattern) => write!(f, "{pattern}"),
+        }
+    }
+}
+
+impl DateFormatter {
+    /// Builds a date formatter, converting dates to `timezone` if set.
+    pub const fn new(format: DateFormat, timezone: Option<Tz>) -> Self {
+        Self { format, timezone }
+    }
+
+    /// Formats a date.
+    pub fn format(&self, date: DateTime<Local>) -> String {
+        match self.timezone {
+            Some(timezone) => self.format_in(date.with_timezone(&timezone)),
+            None => self.format_in(date),
+        }
+    }
+
+    /// Formats a date in its timezone.
+    fn format_in<T: TimeZone>(&self, date: DateTime<T>) -> String
+    where
+        T::Offset: fmt::Display,
+    {
+        match &self.format {
+            DateFormat::Rfc2822 => date.to_rfc2822(),
+            DateFormat::Relative => relative(date),
+            DateFormat::Iso8601 => date.to_rfc3339(),
+            DateFormat::Custom(pattern) => date.format(pattern).to_string(),
+        }
+    }
+}
+
+/// Returns the age of a date relative to now, like `3 h ago`.
+pub fn age(date: DateTime<Local>) -> String {
+    let age = Local::now().signed_duration_since(date);
+
+    if age.num_days() > 0 {
+        format!("{} d ago", age.num_days())
+    } else if age.num_hours() > 0 {
+        format!("{} h ago", age.num_hours())
+    } else if age.num_minutes() > 0 {
+        format!("{} min ago", age.num_minutes())
+    } else {
+        "just now".to_owned()
+    }
+}
+
+/// Formats a date relative to now.
+///
+/// Dates from today are shown as an age, and older dates with the day of the
+/// week for the past week, or in full otherwise. Future dates are shown in full.
+fn relative<T: TimeZone>(date: DateTime<T>) -> String
+where
+    T::Offset: fmt::Display,
+{
+    let now = Local::now().with_timezone(&date.timezone());
+    let days = now.date_naive().num_days_from_ce()
+        - date.date_naive().num_days_from_ce();
+
+    if date > now {
+        date.format(FULL_FORMAT).to_string()
+    } else if days == 0 {
+        age(date.with_timezone(&Local))
+    } else if days == 1 {
+        format!("yesterday {}", date.format(TIME_FORMAT))
+    } else if i64::from(days) <= WEEKDAY_DAYS {
+        date.format(WEEKDAY_FORMAT).to_string()
+    } else {
+        date.format(FULL_FORMAT).to_string()
+    }
+}
+
+/// Returns whether a `strftime` pattern is valid.
+fn is_valid_pattern(pattern: &str) -> bool {
+    pattern.contains('%')
+        && StrftimeItems::new(pattern).all(|item| item != Item::Error)
+}
@@ -0,0 +1,93 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Safe file writes.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    process,
+};
+
+/// An advisory lock on a file, released when dropped.
+#[derive(Debug)]
+pub struct Lock {
+    _file: File,
+}
+
+impl Lock {
+    /// Acquires an exclusive lock on a file, waiting for it if necessary.
+    ///
+    /// The file is created if it does not exist.
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+
+        file.lock()?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// Writes a file atomically.
+///
+/// The contents are written to a temporary file in the same directory, which
+/// is then renamed to `path`. On Unix, the file is created with `mode` if set.
+pub fn write_atomic(
+    path: &Path,
+    contents: &str,
+    mode: Option<u32>,
+) -> io::Result<()> {
+    let mut tmp_path = OsString::from(path);
+    tmp_path.push(format!(".tmp-{}", process::id()));
+
+    let result = write_and_rename(path, tmp_path.as_ref(), contents, mode);
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+/// Writes the contents to `tmp_path`, then renames it to `path`.
+fn write_and_rename(
+    path: &Path,
+    tmp_path: &Path,
+    contents: &str,
+    mode: Option<u32>,
+) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.create(true).truncate(true).write(true);
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let mut file = options.open(tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+
+    fs::rename(tmp_path, path)
+}
@@ -22,10 +22,17 @@
 #![deny(unused_must_use)]
 #![forbid(unsafe_code)]
 
-mod client;
+mod cache;
+mod carrier;
 mod command;
 mod config;
+mod dates;
+mod file;
+mod notifier;
+mod output;
 mod state;
+mod tracking_number;
+mod versioned;
 mod views;
 
 #[doc(hidden)]
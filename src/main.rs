@@ -28,6 +28,5 @@ use eyre::Result;
 use track::Track;
 
 fn main() -> Result<()> {
-    color_eyre::install()?;
     Track::run()
 }
@@ -0,0 +1,225 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Desktop notifications.
+//!
+//! Notifications are sent through the `org.freedesktop.Notifications` D-Bus
+//! interface on the session bus. This requires the `notifications` feature.
+
+#[cfg(feature = "notifications")]
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+/// A desktop notifier.
+#[derive(Debug)]
+pub struct Notifier {
+    #[cfg(feature = "notifications")]
+    connection: zbus::blocking::Connection,
+}
+
+/// An error that can occur when sending notifications.
+#[derive(Debug, Error)]
+pub enum NotifierError {
+    #[cfg(not(feature = "notifications"))]
+    #[error("track has been built without support for notifications")]
+    Unsupported,
+    #[cfg(feature = "notifications")]
+    #[error("error while connecting to the session bus")]
+    ConnectionError(#[source] zbus::Error),
+    #[cfg(feature = "notifications")]
+    #[error("error while sending a notification")]
+    NotifyError(#[source] zbus::Error),
+}
+
+/// The well-known name of the notification server.
+#[cfg(feature = "notifications")]
+const DESTINATION: &str = "org.freedesktop.Notifications";
+
+/// The object path of the notification server.
+#[cfg(feature = "notifications")]
+const PATH: &str = "/org/freedesktop/Notifications";
+
+/// The name of the application sending the notifications.
+#[cfg(feature = "notifications")]
+const APP_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Let the notification server choose when the notification expires.
+#[cfg(feature = "notifications")]
+const DEFAULT_EXPIRE_TIMEOUT: i32 = -1;
+
+#[cfg(feature = "notifications")]
+impl Notifier {
+    /// Connects to the notification server on the session bus.
+    pub fn new() -> Result<Self, NotifierError> {
+        let connection = zbus::blocking::Connection::session()
+            .map_err(NotifierError::ConnectionError)?;
+        Ok(Self { connection })
+    }
+
+    /// Sends a notification.
+    pub fn notify(
+        &self,
+        summary: &str,
+        body: &str,
+    ) -> Result<(), NotifierError> {
+        let replaces_id = 0_u32;
+        let app_icon = "";
+        let actions: &[&str] = &[];
+        let hints = HashMap::<&str, zbus::zvariant::Value<'_>>::new();
+
+        self.connection
+            .call_method(
+                Some(DESTINATION),
+                PATH,
+                Some(DESTINATION),
+                "Notify",
+                &(
+                    APP_NAME,
+                    replaces_id,
+                    app_icon,
+                    summary,
+                    body,
+                    actions,
+                    hints,
+                    DEFAULT_EXPIRE_TIMEOUT,
+                ),
+            )
+            .map_err(NotifierError::NotifyError)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "notifications"))]
+impl Notifier {
+    /// Fails, as `track` has been built without support for notifications.
+    pub const fn new() -> Result<Self, NotifierError> {
+        Err(NotifierError::Unsupported)
+    }
+
+    /// Does nothing, as a notifier cannot be built.
+    pub const fn notify(
+        &self,
+        _summary: &str,
+        _body: &str,
+    ) -> Result<(), NotifierError> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "notifications"))]
+mod tests {
+    use std::{
+        env,
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::{Arc, Mutex},
+    };
+
+    use zbus::{dbus_interface, zvariant::Value};
+
+    use super::*;
+
+    /// A session bus started with `dbus-daemon`, stopped when dropped.
+    struct SessionBus(Child);
+
+    impl SessionBus {
+        /// Starts a session bus and returns it with its address.
+        fn start() -> (Self, String) {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus-daemon to start");
+
+            let stdout = daemon.stdout.take().expect("a piped stdout");
+            let mut address = String::new();
+            BufReader::new(stdout)
+                .read_line(&mut address)
+                .expect("the address of the bus");
+
+            (Self(daemon), address.trim().to_owned())
+        }
+    }
+
+    impl Drop for SessionBus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    /// A stub notification server, recording the notifications.
+    #[derive(Default)]
+    struct NotificationServer {
+        notifications: Arc<Mutex<Vec<(String, String, String)>>>,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.Notifications")]
+    impl NotificationServer {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            app_name: &str,
+            _replaces_id: u32,
+            _app_icon: &str,
+            summary: &str,
+            body: &str,
+            _actions: Vec<&str>,
+            _hints: HashMap<&str, Value<'_>>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let mut notifications =
+                self.notifications.lock().expect("unpoisoned");
+            notifications.push((
+                app_name.to_owned(),
+                summary.to_owned(),
+                body.to_owned(),
+            ));
+            u32::try_from(notifications.len()).expect("a few notifications")
+        }
+    }
+
+    #[test]
+    #[ignore = "requires dbus-daemon"]
+    fn notify_reaches_the_notification_server() {
+        let (_bus, address) = SessionBus::start();
+        env::set_var("DBUS_SESSION_BUS_ADDRESS", &address);
+
+        let server = NotificationServer::default();
+        let notifications = Arc::clone(&server.notifications);
+        let _server =
+            zbus::blocking::ConnectionBuilder::address(address.as_str())
+                .and_then(|builder| builder.name(DESTINATION))
+                .and_then(|builder| builder.serve_at(PATH, server))
+                .and_then(zbus::blocking::ConnectionBuilder::build)
+                .expect("the stub server to be on the bus");
+
+        let notifier = Notifier::new().expect("a connection to the bus");
+        notifier
+            .notify("Shoes (6A12345678901)", "Pris en charge")
+            .expect("a sent notification");
+
+        assert_eq!(
+            *notifications.lock().expect("unpoisoned"),
+            [(
+                APP_NAME.to_owned(),
+                "Shoes (6A12345678901)".to_owned(),
+                "Pris en charge".to_owned(),
+            )]
+        );
+    }
+}
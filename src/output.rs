@@ -0,0 +1,325 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Machine-readable output.
+//!
+//! The records defined here are the documented schema of the JSON, YAML and
+//! CSV outputs. Fields can be added, but existing ones must not be changed.
+
+use std::io;
+
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    carrier::{CarrierId, Event, FetchError, Shipment},
+    state::Parcel,
+};
+
+/// An output format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// JSON.
+    Json,
+    /// YAML.
+    Yaml,
+    /// CSV, with a header line.
+    Csv,
+}
+
+/// A tracked parcel.
+#[derive(Debug, Serialize)]
+pub struct ParcelRecord {
+    tracking_number: String,
+    description: String,
+    carrier: CarrierId,
+    added_at: Option<DateTime<Local>>,
+    last_status: Option<String>,
+    notes: Option<String>,
+    tags: Vec<String>,
+    delivered: bool,
+}
+
+/// The tracking info of a parcel.
+#[derive(Debug, Serialize)]
+pub struct TrackingRecord {
+    tracking_number: String,
+    description: Option<String>,
+    delivered: Option<bool>,
+    error: Option<String>,
+    events: Vec<EventRecord>,
+}
+
+/// An event, in a `TrackingRecord`.
+#[derive(Debug, Serialize)]
+pub struct EventRecord {
+    date: DateTime<Local>,
+    label: String,
+    code: Option<String>,
+}
+
+/// A line of the CSV output for tracking info.
+///
+/// There is one line per event, or a single line with an error.
+#[derive(Debug, Serialize)]
+struct EventRow<'a> {
+    tracking_number: &'a str,
+    description: Option<&'a str>,
+    date: Option<DateTime<Local>>,
+    label: Option<&'a str>,
+    code: Option<&'a str>,
+    error: Option<&'a str>,
+}
+
+/// A line of the CSV output for parcels.
+#[derive(Debug, Serialize)]
+struct ParcelRow<'a> {
+    tracking_number: &'a str,
+    description: &'a str,
+    carrier: CarrierId,
+    added_at: Option<DateTime<Local>>,
+    last_status: Option<&'a str>,
+    notes: Option<&'a str>,
+    tags: String,
+    delivered: bool,
+}
+
+/// An error that can occur when writing the output.
+#[derive(Debug, Error)]
+pub enum OutputError {
+    #[error("error while serialising to JSON")]
+    Json(#[from] serde_json::Error),
+    #[error("error while serialising to YAML")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("error while serialising to CSV")]
+    Csv(#[from] csv::Error),
+    #[error("error while writing the output")]
+    Io(#[from] io::Error),
+}
+
+/// The separator of tags in the CSV output.
+const CSV_TAG_SEPARATOR: &str = ";";
+
+/// The header of the CSV output for tracking info, matching `EventRow`.
+const EVENT_CSV_HEADER: [&str; 6] = [
+    "tracking_number",
+    "description",
+    "date",
+    "label",
+    "code",
+    "error",
+];
+
+/// The header of the CSV output for parcels, matching `ParcelRow`.
+const PARCEL_CSV_HEADER: [&str; 8] = [
+    "tracking_number",
+    "description",
+    "carrier",
+    "added_at",
+    "last_status",
+    "notes",
+    "tags",
+    "delivered",
+];
+
+impl Format {
+    /// Returns whether the format is machine-readable.
+    pub fn is_machine_readable(self) -> bool {
+        self != Self::Text
+    }
+}
+
+impl ParcelRecord {
+    /// Builds a record for a tracked parcel.
+    pub fn new(tracking_number: &str, parcel: &Parcel) -> Self {
+        Self {
+            tracking_number: tracking_number.to_owned(),
+            description: parcel.description.clone(),
+            carrier: parcel.carrier,
+            added_at: parcel.added_at,
+            last_status: parcel.last_status.clone(),
+            notes: parcel.notes.clone(),
+            tags: parcel.tags.clone(),
+            delivered: parcel.delivered,
+        }
+    }
+}
+
+impl TrackingRecord {
+    /// Builds a record for the tracking info of a parcel.
+    ///
+    /// Events are listed from the oldest to the most recent.
+    pub fn new(
+        tracking_number: &str,
+        description: Option<&str>,
+        result: Result<&Shipment, &FetchError>,
+    ) -> Self {
+        let (delivered, error, events) = match result {
+            Ok(shipment) => {
+                let mut events = shipment
+                    .events
+                    .iter()
+                    .map(EventRecord::from)
+                    .collect::<Vec<_>>();
+                events.sort_by_key(|event| event.date);
+                (Some(shipment.delivered), None, events)
+            }
+            Err(error) => {
+                let error = match error.api_message() {
+                    Some(message) => format!("{error} ({message})"),
+                    None => error.to_string(),
+                };
+                (None, Some(error), vec![])
+            }
+        };
+
+        Self {
+            tracking_number: tracking_number.to_owned(),
+            description: description.map(ToOwned::to_owned),
+            delivered,
+            error,
+            events,
+        }
+    }
+}
+
+impl From<&Event> for EventRecord {
+    fn from(event: &Event) -> Self {
+        Self {
+            date: event.date,
+            label: event.label.clone(),
+            code: event.code.clone(),
+        }
+    }
+}
+
+/// Prints tracked parcels in a machine-readable format.
+pub fn print_parcels(
+    format: Format,
+    parcels: &[ParcelRecord],
+) -> Result<(), OutputError> {
+    match format {
+        Format::Csv => {
+            let mut writer = csv_writer(PARCEL_CSV_HEADER)?;
+            for parcel in parcels {
+                writer.serialize(ParcelRow::from(parcel))?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        _ => print_serialised(format, parcels),
+    }
+}
+
+/// Prints tracking info in a machine-readable format.
+pub fn print_tracking_info(
+    format: Format,
+    records: &[TrackingRecord],
+) -> Result<(), OutputError> {
+    match format {
+        Format::Csv => {
+            let mut writer = csv_writer(EVENT_CSV_HEADER)?;
+            for record in records {
+                for row in EventRow::from_record(record) {
+                    writer.serialize(row)?;
+                }
+            }
+            writer.flush()?;
+            Ok(())
+        }
+        _ => print_serialised(format, records),
+    }
+}
+
+/// Builds a CSV writer on the standard output and writes the header.
+///
+/// The header is written explicitly so that it is present even without rows.
+fn csv_writer<const N: usize>(
+    header: [&str; N],
+) -> Result<csv::Writer<io::Stdout>, OutputError> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(io::stdout());
+    writer.write_record(header)?;
+    Ok(writer)
+}
+
+/// Prints a value in JSON or YAML.
+fn print_serialised<T: Serialize + ?Sized>(
+    format: Format,
+    value: &T,
+) -> Result<(), OutputError> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(io::stdout(), value)?;
+            println!();
+        }
+        Format::Yaml => serde_yaml::to_writer(io::stdout(), value)?,
+        Format::Text | Format::Csv => {
+            unreachable!("not a serialisation format: {format:?}")
+        }
+    }
+
+    Ok(())
+}
+
+impl<'a> EventRow<'a> {
+    /// Builds the CSV lines for a tracking record.
+    fn from_record(record: &'a TrackingRecord) -> Vec<Self> {
+        let row = Self {
+            tracking_number: &record.tracking_number,
+            description: record.description.as_deref(),
+            date: None,
+            label: None,
+            code: None,
+            error: record.error.as_deref(),
+        };
+
+        if record.events.is_empty() {
+            return vec![row];
+        }
+
+        record
+            .events
+            .iter()
+            .map(|event| Self {
+                date: Some(event.date),
+                label: Some(&event.label),
+                code: event.code.as_deref(),
+                ..row
+            })
+            .collect()
+    }
+}
+
+impl<'a> From<&'a ParcelRecord> for ParcelRow<'a> {
+    fn from(parcel: &'a ParcelRecord) -> Self {
+        Self {
+            tracking_number: &parcel.tracking_number,
+            description: &parcel.description,
+            carrier: parcel.carrier,
+            added_at: parcel.added_at,
+            last_status: parcel.last_status.as_deref(),
+            notes: parcel.notes.as_deref(),
+            tags: parcel.tags.join(CSV_TAG_SEPARATOR),
+            delivered: parcel.delivered,
+        }
+    }
+}
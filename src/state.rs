@@ -13,19 +13,33 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{collections::HashMap, fs, io, path::PathBuf};
+use std::{collections::HashMap, fs, io, mem, path::PathBuf, time::Duration};
 
+use chrono::{DateTime, Local};
+use derive_new::new;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use toml::{Table, Value};
+
+use crate::{
+    carrier::{CarrierId, Shipment},
+    file::{self, Lock},
+    versioned::{self, Migration, UpgradeError},
+};
+
 /// The persistent state for `track`.
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct State {
+    version: u32,
+    #[serde(default)]
     parcels: Parcels,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    archive: Parcels,
 }
 
 /// A set of parcels.
-type Parcels = HashMap<TrackingNumber, Description>;
+type Parcels = HashMap<TrackingNumber, Parcel>;
 
 /// A tracking number.
 type TrackingNumber = String;
@@ -33,6 +47,54 @@ type TrackingNumber = String;
 /// A parcel description.
 type Description = String;
 
+/// A tracked parcel.
+#[derive(new, Debug, Clone, Serialize, Deserialize)]
+pub struct Parcel {
+    /// The description of the parcel.
+    pub description: Description,
+
+    /// The carrier of the parcel.
+    #[serde(default)]
+    pub carrier: CarrierId,
+
+    /// When the parcel has been added.
+    ///
+    /// This is unknown for parcels added by older versions of `track`.
+    #[new(value = "Some(Local::now())")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_at: Option<DateTime<Local>>,
+
+    /// The label of the last known event.
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_status: Option<String>,
+
+    /// The date of the most recent event seen by the user.
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen_event: Option<DateTime<Local>>,
+
+    /// Some notes about the parcel.
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// Tags to organise the parcels.
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Whether the parcel has been delivered.
+    #[new(default)]
+    #[serde(default)]
+    pub delivered: bool,
+
+    /// When the delivery has been detected.
+    #[new(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<DateTime<Local>>,
+}
+
 /// An error that can occur when loading the state.
 #[derive(Debug, Error)]
 pub enum LoadError {
@@ -41,7 +103,18 @@ pub enum LoadError {
     #[error("error while reading the state file")]
     ReadError(#[from] io::Error),
     #[error("error while parsing the state file")]
-    ParseError(#[from] toml::de::Error),
+    ParseError(#[source] toml::de::Error),
+    #[error("invalid version in the state file")]
+    InvalidVersion,
+    #[error(
+        "the state file is from a newer version of track (version {found}, \
+         supported up to {supported})"
+    )]
+    NewerVersion { found: u32, supported: u32 },
+    #[error("error while backing up the state file")]
+    BackupError(#[source] io::Error),
+    #[error("error while saving the migrated state file")]
+    SaveError(#[from] SaveError),
 }
 
 /// An error that can occur when saving the state.
@@ -53,6 +126,15 @@ pub enum SaveError {
     FsError(#[from] io::Error),
 }
 
+/// An error that can occur when locking the state.
+#[derive(Debug, Error)]
+pub enum LockError {
+    #[error("impossible to locate the state file")]
+    NoDataDir(#[from] DataDirError),
+    #[error("error while locking the state file")]
+    FsError(#[from] io::Error),
+}
+
 /// An error that can occur when getting the data directory.
 #[derive(Debug, Error)]
 pub enum DataDirError {
@@ -63,16 +145,53 @@ pub enum DataDirError {
 /// The name of the state file.
 const STATE_FILE_NAME: &str = "state.toml";
 
+/// The name of the lock file for the state.
+const LOCK_FILE_NAME: &str = "state.lock";
+
+/// The migrations of the state file, from version 0.
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            version: versioned::current_version(MIGRATIONS),
+            parcels: Parcels::default(),
+            archive: Parcels::default(),
+        }
+    }
+}
+
 impl State {
     /// Loads the state.
     pub fn load() -> Result<Self, LoadError> {
-        match fs::read_to_string(state_file()?) {
-            Ok(state) => Ok(toml::from_str(&state)?),
+        let state_file = state_file()?;
+
+        let contents = match fs::read_to_string(&state_file) {
+            Ok(contents) => contents,
             Err(e) => match e.kind() {
-                io::ErrorKind::NotFound => Ok(Self::default()),
-                _ => Err(e.into()),
+                io::ErrorKind::NotFound => return Ok(Self::default()),
+                _ => return Err(e.into()),
             },
+        };
+
+        let upgraded = versioned::upgrade::<Self>(&contents, MIGRATIONS)?;
+
+        if upgraded.migrated_from.is_some() {
+            versioned::backup(&state_file, &contents, None)
+                .map_err(LoadError::BackupError)?;
+            upgraded.value.save()?;
         }
+
+        Ok(upgraded.value)
+    }
+
+    /// Locks the state until the returned lock is dropped.
+    ///
+    /// This must be held around any load-modify-save cycle, so that concurrent
+    /// instances of `track` do not overwrite each other's changes.
+    pub fn lock() -> Result<Lock, LockError> {
+        fs::create_dir_all(data_dir()?)?;
+        Ok(Lock::acquire(&data_dir()?.join(LOCK_FILE_NAME))?)
     }
 
     /// Saves the state.
@@ -81,26 +200,48 @@ impl State {
 
         let state =
             toml::to_string(self).expect("failed to serialise the state");
-        fs::write(state_file()?, state)?;
+        file::write_atomic(&state_file()?, &state, None)?;
 
         Ok(())
     }
 
     /// Adds a parcel to track.
+    ///
+    /// If the parcel is already tracked, its description and carrier are
+    /// updated and the old description is returned. Archived parcels are
+    /// restored first.
     pub fn add_parcel(
         &mut self,
         tracking_number: &str,
-        description: &str,
+        parcel: Parcel,
     ) -> Option<Description> {
-        self.parcels
-            .insert(tracking_number.to_owned(), description.to_owned())
+        self.restore_parcel(tracking_number);
+
+        match self.parcels.get_mut(tracking_number) {
+            Some(existing) => {
+                existing.carrier = parcel.carrier;
+                existing.notes = parcel.notes.or(existing.notes.take());
+                for tag in parcel.tags {
+                    if !existing.tags.contains(&tag) {
+                        existing.tags.push(tag);
+                    }
+                }
+
+                Some(mem::replace(
+                    &mut existing.description,
+                    parcel.description,
+                ))
+            }
+
+            None => {
+                self.parcels.insert(tracking_number.to_owned(), parcel);
+                None
+            }
+        }
     }
 
     /// Removes a parcel.
-    pub fn remove_parcel(
-        &mut self,
-        tracking_number: &str,
-    ) -> Option<Description> {
+    pub fn remove_parcel(&mut self, tracking_number: &str) -> Option<Parcel> {
         self.parcels.remove(tracking_number)
     }
 
@@ -108,10 +249,175 @@ impl State {
     pub fn parcels(&self) -> &Parcels {
         &self.parcels
     }
+
+    /// Returns a tracked parcel.
+    pub fn parcel(&self, tracking_number: &str) -> Option<&Parcel> {
+        self.parcels.get(tracking_number)
+    }
+
+    /// Updates the last known status and last seen event of a parcel from its
+    /// shipment.
+    pub fn update_status(
+        &mut self,
+        tracking_number: &str,
+        shipment: &Shipment,
+    ) {
+        if let Some(parcel) = self.parcels.get_mut(tracking_number) {
+            if let Some(event) = shipment.latest_event() {
+                parcel.last_status = Some(event.label.clone());
+                parcel.last_seen_event = Some(event.date);
+            }
+
+            parcel.delivered = shipment.delivered;
+
+            if !parcel.delivered {
+                parcel.delivered_at = None;
+            } else if parcel.delivered_at.is_none() {
+                parcel.delivered_at = Some(Local::now());
+            }
+        }
+    }
+
+    /// Returns the tracked parcels, sorted by description and tracking number.
+    pub fn sorted_parcels(&self) -> Vec<(&TrackingNumber, &Parcel)> {
+        sorted(&self.parcels)
+    }
+
+    /// Returns the archived parcels, sorted by description and tracking number.
+    pub fn sorted_archive(&self) -> Vec<(&TrackingNumber, &Parcel)> {
+        sorted(&self.archive)
+    }
+
+    /// Moves the parcels delivered for more than `grace_period` to the archive.
+    ///
+    /// The archived parcels are returned.
+    pub fn archive_delivered(
+        &mut self,
+        grace_period: Duration,
+    ) -> Vec<(TrackingNumber, Parcel)> {
+        let now = Local::now();
+        let to_archive = self
+            .parcels
+            .iter()
+            .filter(|(_, parcel)| {
+                parcel.delivered_at.is_some_and(|delivered_at| {
+                    (now - delivered_at)
+                        .to_std()
+                        .is_ok_and(|elapsed| elapsed >= grace_period)
+                })
+            })
+            .map(|(tracking_number, _)| tracking_number.clone())
+            .collect::<Vec<_>>();
+
+        to_archive
+            .into_iter()
+            .filter_map(|tracking_number| {
+                let parcel = self.parcels.remove(&tracking_number)?;
+                self.archive.insert(tracking_number.clone(), parcel.clone());
+                Some((tracking_number, parcel))
+            })
+            .collect()
+    }
+
+    /// Moves a parcel from the archive back to the tracked parcels.
+    ///
+    /// The grace period before archiving it again starts over.
+    pub fn restore_parcel(&mut self, tracking_number: &str) -> Option<&Parcel> {
+        let mut parcel = self.archive.remove(tracking_number)?;
+
+        if parcel.delivered {
+            parcel.delivered_at = Some(Local::now());
+        }
+
+        self.parcels.insert(tracking_number.to_owned(), parcel);
+        self.parcels.get(tracking_number)
+    }
+
+    /// Removes the parcels archived after a delivery older than `min_age`.
+    ///
+    /// The pruned parcels are returned.
+    pub fn prune_archive(
+        &mut self,
+        min_age: Duration,
+    ) -> Vec<(TrackingNumber, Parcel)> {
+        let now = Local::now();
+        let (pruned, kept) = mem::take(&mut self.archive)
+            .into_iter()
+            .partition(|(_, parcel)| {
+                parcel.delivered_at.is_none_or(|delivered_at| {
+                    (now - delivered_at)
+                        .to_std()
+                        .is_ok_and(|elapsed| elapsed >= min_age)
+                })
+            });
+
+        self.archive = kept;
+        pruned.into_iter().collect()
+    }
+}
+
+/// Sorts parcels by description and tracking number.
+fn sorted(parcels: &Parcels) -> Vec<(&TrackingNumber, &Parcel)> {
+    let mut parcels = parcels.iter().collect::<Vec<_>>();
+    parcels.sort_by(|(number_a, parcel_a), (number_b, parcel_b)| {
+        parcel_a
+            .description
+            .cmp(&parcel_b.description)
+            .then_with(|| number_a.cmp(number_b))
+    });
+    parcels
+}
+
+/// Migrates the state from version 0 to version 1.
+///
+/// In version 0, parcels were bare descriptions, with their carrier stored in
+/// a separate `carriers` table.
+fn migrate_v0_to_v1(state: &mut Table) {
+    let carriers = match state.remove("carriers") {
+        Some(Value::Table(carriers)) => carriers,
+        _ => Table::new(),
+    };
+
+    let Some(Value::Table(parcels)) = state.get_mut("parcels") else {
+        return;
+    };
+
+    for (tracking_number, parcel) in parcels.iter_mut() {
+        if let Value::String(description) = parcel {
+            let mut record = Table::new();
+            record.insert(
+                "description".to_owned(),
+                Value::String(mem::take(description)),
+            );
+
+            if let Some(carrier) = carriers.get(tracking_number) {
+                record.insert("carrier".to_owned(), carrier.clone());
+            }
+
+            *parcel = Value::Table(record);
+        }
+    }
+}
+
+/// Migrates the state from version 1 to version 2.
+///
+/// Version 2 adds the archive, which older versions of `track` would drop.
+fn migrate_v1_to_v2(_state: &mut Table) {}
+
+impl From<UpgradeError> for LoadError {
+    fn from(error: UpgradeError) -> Self {
+        match error {
+            UpgradeError::ParseError(e) => Self::ParseError(e),
+            UpgradeError::InvalidVersion => Self::InvalidVersion,
+            UpgradeError::NewerVersion { found, supported } => {
+                Self::NewerVersion { found, supported }
+            }
+        }
+    }
 }
 
 /// Gets the data directory for `track`.
-fn data_dir() -> Result<PathBuf, DataDirError> {
+pub fn data_dir() -> Result<PathBuf, DataDirError> {
     Ok(dirs::data_dir()
         .ok_or(DataDirError::NoDataDir)?
         .join(env!("CARGO_PKG_NAME")))
@@ -121,3 +427,113 @@ fn data_dir() -> Result<PathBuf, DataDirError> {
 fn state_file() -> Result<PathBuf, DataDirError> {
     Ok(data_dir()?.join(STATE_FILE_NAME))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V0_STATE: &str = r#"
+        [parcels]
+        6A12345678901 = "Shoes"
+        RR123456785FR = "Book"
+
+        [carriers]
+        RR123456785FR = "la-poste"
+    "#;
+
+    #[test]
+    fn v0_state_is_upgraded_to_the_current_version() {
+        let upgraded =
+            versioned::upgrade::<State>(V0_STATE, MIGRATIONS).expect("valid");
+        let state = upgraded.value;
+
+        assert_eq!(upgraded.migrated_from, Some(0));
+        assert_eq!(state.version, 2);
+        assert_eq!(state.parcels.len(), 2);
+        assert!(state.archive.is_empty());
+
+        let shoes = state.parcel("6A12345678901").expect("migrated parcel");
+        assert_eq!(shoes.description, "Shoes");
+        assert_eq!(shoes.carrier, CarrierId::LaPoste);
+        assert_eq!(shoes.added_at, None);
+
+        let book = state.parcel("RR123456785FR").expect("migrated parcel");
+        assert_eq!(book.description, "Book");
+        assert_eq!(book.carrier, CarrierId::LaPoste);
+    }
+
+    #[test]
+    fn v0_migration_folds_the_carriers_into_the_parcels() {
+        let mut table = V0_STATE.parse::<Table>().expect("valid TOML");
+
+        migrate_v0_to_v1(&mut table);
+
+        assert!(!table.contains_key("carriers"));
+        let parcels = table["parcels"].as_table().expect("a table");
+        assert_eq!(
+            parcels["6A12345678901"]["description"].as_str(),
+            Some("Shoes")
+        );
+        assert!(parcels["6A12345678901"].get("carrier").is_none());
+        assert_eq!(
+            parcels["RR123456785FR"]["carrier"].as_str(),
+            Some("la-poste")
+        );
+    }
+
+    #[test]
+    fn current_state_is_not_migrated() {
+        let contents = r#"
+            version = 2
+
+            [parcels.6A12345678901]
+            description = "Shoes"
+            carrier = "la-poste"
+        "#;
+
+        let upgraded =
+            versioned::upgrade::<State>(contents, MIGRATIONS).expect("valid");
+
+        assert_eq!(upgraded.migrated_from, None);
+        assert_eq!(upgraded.value.parcels.len(), 1);
+    }
+
+    fn shipment(delivered: bool) -> Shipment {
+        serde_json::from_value(serde_json::json!({
+            "is_final": delivered,
+            "delivered": delivered,
+            "events": [],
+            "timeline": [],
+        }))
+        .expect("a valid shipment")
+    }
+
+    #[test]
+    fn parcel_no_longer_delivered_is_not_archived() {
+        let mut state = State::default();
+        let parcel = Parcel::new("Shoes".to_owned(), CarrierId::LaPoste);
+        state.add_parcel("6A12345678901", parcel);
+
+        state.update_status("6A12345678901", &shipment(true));
+        state.update_status("6A12345678901", &shipment(false));
+
+        let parcel = state.parcel("6A12345678901").expect("tracked parcel");
+        assert!(!parcel.delivered);
+        assert_eq!(parcel.delivered_at, None);
+        assert!(state.archive_delivered(Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn delivered_parcel_is_archived_after_the_grace_period() {
+        let mut state = State::default();
+        let parcel = Parcel::new("Shoes".to_owned(), CarrierId::LaPoste);
+        state.add_parcel("6A12345678901", parcel);
+
+        state.update_status("6A12345678901", &shipment(true));
+
+        assert!(state
+            .archive_delivered(Duration::from_secs(3600))
+            .is_empty());
+        assert_eq!(state.archive_delivered(Duration::ZERO).len(), 1);
+    }
+}
@@ -0,0 +1,229 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Tracking number formats and validation.
+
+use thiserror::Error;
+
+/// A tracking number format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A Colissimo parcel, like `6A12345678901`.
+    Colissimo,
+    /// A Lettre suivie or other tracked letter, like `1K12345678901`.
+    LettreSuivie,
+    /// A Chronopost parcel, like `XA123456785FR`.
+    Chronopost,
+    /// A UPU S10 international item, like `RR123456785FR`.
+    S10,
+}
+
+/// An error that can occur when validating a tracking number.
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("The tracking number is empty.")]
+    Empty,
+    #[error("{0} does not match any known tracking number format.")]
+    UnknownFormat(String),
+    #[error("{0} has an invalid check digit.")]
+    InvalidCheckDigit(String),
+}
+
+/// The weights used to compute the check digit of a S10 identifier.
+const S10_WEIGHTS: [u32; 8] = [8, 6, 4, 2, 3, 5, 9, 7];
+
+/// Normalises a tracking number by removing spaces and making it uppercase.
+pub fn normalise(tracking_number: &str) -> String {
+    tracking_number
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// Validates a tracking number and returns its format.
+///
+/// The tracking number is normalised before validation.
+pub fn validate(tracking_number: &str) -> Result<Format, ValidationError> {
+    let tracking_number = normalise(tracking_number);
+
+    if tracking_number.is_empty() {
+        return Err(ValidationError::Empty);
+    }
+
+    let format = detect(&tracking_number).ok_or_else(|| {
+        ValidationError::UnknownFormat(tracking_number.clone())
+    })?;
+
+    if matches!(format, Format::Chronopost | Format::S10)
+        && !has_valid_s10_check_digit(&tracking_number)
+    {
+        return Err(ValidationError::InvalidCheckDigit(tracking_number));
+    }
+
+    Ok(format)
+}
+
+/// Detects the format of a normalised tracking number.
+///
+/// The check digit of S10 identifiers is not verified.
+pub fn detect(tracking_number: &str) -> Option<Format> {
+    let bytes = tracking_number.as_bytes();
+
+    match bytes {
+        // Domestic La Poste numbers: a product digit, a product letter and 11
+        // digits.
+        [product, letter, rest @ ..]
+            if rest.len() == 11
+                && product.is_ascii_digit()
+                && letter.is_ascii_uppercase()
+                && all_digits(rest) =>
+        {
+            match product {
+                b'1'..=b'4' => Some(Format::LettreSuivie),
+                b'5'..=b'9' => Some(Format::Colissimo),
+                _ => None,
+            }
+        }
+
+        // S10 identifiers: a two-letter service indicator, an 8-digit serial
+        // number, a check digit and a two-letter country code.
+        [s1, s2, digits @ .., c1, c2]
+            if digits.len() == 9
+                && all_uppercase(&[*s1, *s2, *c1, *c2])
+                && all_digits(digits) =>
+        {
+            // Express items sent from France are handled by Chronopost.
+            if [*c1, *c2] == *b"FR" && matches!(s1, b'E' | b'X') {
+                Some(Format::Chronopost)
+            } else {
+                Some(Format::S10)
+            }
+        }
+
+        _ => None,
+    }
+}
+
+/// Returns whether a S10 identifier has a valid check digit.
+fn has_valid_s10_check_digit(tracking_number: &str) -> bool {
+    let digits = tracking_number
+        .bytes()
+        .skip(2)
+        .take(9)
+        .map(|b| u32::from(b - b'0'))
+        .collect::<Vec<_>>();
+
+    let Some((&check_digit, serial)) = digits.split_last() else {
+        return false;
+    };
+
+    let sum: u32 = serial.iter().zip(S10_WEIGHTS).map(|(d, w)| d * w).sum();
+    let expected = match 11 - sum % 11 {
+        10 => 0,
+        11 => 5,
+        digit => digit,
+    };
+
+    check_digit == expected
+}
+
+/// Returns whether all bytes are ASCII digits.
+fn all_digits(bytes: &[u8]) -> bool {
+    bytes.iter().all(u8::is_ascii_digit)
+}
+
+/// Returns whether all bytes are ASCII uppercase letters.
+fn all_uppercase(bytes: &[u8]) -> bool {
+    bytes.iter().all(u8::is_ascii_uppercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_domestic_numbers() {
+        assert_eq!(detect("6A12345678901"), Some(Format::Colissimo));
+        assert_eq!(detect("8K12345678901"), Some(Format::Colissimo));
+        assert_eq!(detect("1K12345678901"), Some(Format::LettreSuivie));
+        assert_eq!(detect("4A12345678901"), Some(Format::LettreSuivie));
+    }
+
+    #[test]
+    fn detects_s10_and_chronopost_numbers() {
+        assert_eq!(detect("RR123456785FR"), Some(Format::S10));
+        assert_eq!(detect("EE123456785DE"), Some(Format::S10));
+        assert_eq!(detect("XA123456785FR"), Some(Format::Chronopost));
+        assert_eq!(detect("EE123456785FR"), Some(Format::Chronopost));
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        assert_eq!(detect("0A12345678901"), None);
+        assert_eq!(detect("6A1234567890"), None);
+        assert_eq!(detect("6a12345678901"), None);
+        assert_eq!(detect("RR12345678FR"), None);
+        assert_eq!(detect("R1123456785FR"), None);
+        assert_eq!(detect(""), None);
+    }
+
+    #[test]
+    fn accepts_valid_s10_check_digits() {
+        assert!(has_valid_s10_check_digit("RR123456785FR"));
+        assert!(has_valid_s10_check_digit("RR000000014FR"));
+    }
+
+    #[test]
+    fn maps_a_remainder_of_10_to_a_check_digit_of_0() {
+        assert!(has_valid_s10_check_digit("RR000000080FR"));
+        assert!(!has_valid_s10_check_digit("RR000000081FR"));
+    }
+
+    #[test]
+    fn maps_a_remainder_of_11_to_a_check_digit_of_5() {
+        assert!(has_valid_s10_check_digit("RR000000005FR"));
+        assert!(!has_valid_s10_check_digit("RR000000000FR"));
+    }
+
+    #[test]
+    fn rejects_invalid_s10_check_digits() {
+        assert!(!has_valid_s10_check_digit("RR123456784FR"));
+        assert!(!has_valid_s10_check_digit("RR123456786FR"));
+    }
+
+    #[test]
+    fn validates_normalised_numbers() {
+        assert!(matches!(validate(" rr 1234 5678 5fr "), Ok(Format::S10)));
+        assert!(matches!(
+            validate("6a 1234 5678 901"),
+            Ok(Format::Colissimo)
+        ));
+    }
+
+    #[test]
+    fn reports_validation_errors() {
+        assert!(matches!(validate("  "), Err(ValidationError::Empty)));
+        assert!(matches!(
+            validate("ABC"),
+            Err(ValidationError::UnknownFormat(number)) if number == "ABC"
+        ));
+        assert!(matches!(
+            validate("xa123456784fr"),
+            Err(ValidationError::InvalidCheckDigit(number))
+                if number == "XA123456784FR"
+        ));
+    }
+}
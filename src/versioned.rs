@@ -0,0 +1,185 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Versioned TOML files.
+//!
+//! Each file has a `version` field, starting at 1. Files without one are
+//! considered to be at version 0. When loading a file, migrations upgrade it
+//! step by step up to the current version, which is the number of migrations.
+
+use std::{ffi::OsString, io, path::Path};
+
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use toml::{Table, Value};
+
+use crate::file;
+
+/// A migration from a version to the next one.
+pub type Migration = fn(&mut Table);
+
+/// The name of the version field.
+const VERSION_FIELD: &str = "version";
+
+/// A file upgraded to the current version.
+#[derive(Debug)]
+pub struct Upgraded<T> {
+    /// The deserialised file.
+    pub value: T,
+    /// The version of the file before its migration, if it has been migrated.
+    pub migrated_from: Option<u32>,
+}
+
+/// An error that can occur when upgrading a file.
+#[derive(Debug, Error)]
+pub enum UpgradeError {
+    #[error("error while parsing the file")]
+    ParseError(#[from] toml::de::Error),
+    #[error("invalid version field")]
+    InvalidVersion,
+    #[error("unsupported version {found} (up to {supported} is supported)")]
+    NewerVersion { found: u32, supported: u32 },
+}
+
+/// Parses a file and upgrades it to the current version.
+pub fn upgrade<T: DeserializeOwned>(
+    contents: &str,
+    migrations: &[Migration],
+) -> Result<Upgraded<T>, UpgradeError> {
+    let mut table = contents.parse::<Table>()?;
+    let current = current_version(migrations);
+
+    let version = match table.get(VERSION_FIELD) {
+        None => 0,
+        Some(Value::Integer(version)) => {
+            u32::try_from(*version).map_err(|_| UpgradeError::InvalidVersion)?
+        }
+        Some(_) => return Err(UpgradeError::InvalidVersion),
+    };
+
+    if version > current {
+        return Err(UpgradeError::NewerVersion {
+            found: version,
+            supported: current,
+        });
+    }
+
+    let migrated_from = (version < current).then_some(version);
+
+    for migration in &migrations[version as usize..] {
+        migration(&mut table);
+    }
+
+    table.insert(VERSION_FIELD.to_owned(), Value::Integer(current.into()));
+    let value = Value::Table(table).try_into()?;
+
+    Ok(Upgraded {
+        value,
+        migrated_from,
+    })
+}
+
+/// Returns the current version for a list of migrations.
+pub const fn current_version(migrations: &[Migration]) -> u32 {
+    migrations.len() as u32
+}
+
+/// Keeps a `.bak` copy of a file, with its original contents.
+///
+/// On Unix, the copy is created with `mode` if set.
+pub fn backup(
+    path: &Path,
+    contents: &str,
+    mode: Option<u32>,
+) -> io::Result<()> {
+    let mut backup = OsString::from(path);
+    backup.push(".bak");
+    file::write_atomic(backup.as_ref(), contents, mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct File {
+        version: u32,
+        name: String,
+    }
+
+    const MIGRATIONS: &[Migration] = &[rename_title_to_name];
+
+    fn rename_title_to_name(table: &mut Table) {
+        if let Some(title) = table.remove("title") {
+            table.insert("name".to_owned(), title);
+        }
+    }
+
+    #[test]
+    fn file_without_version_is_migrated_from_version_0() {
+        let upgraded =
+            upgrade::<File>(r#"title = "track""#, MIGRATIONS).expect("valid");
+
+        assert_eq!(upgraded.migrated_from, Some(0));
+        assert_eq!(upgraded.value.version, 1);
+        assert_eq!(upgraded.value.name, "track");
+    }
+
+    #[test]
+    fn file_at_current_version_is_not_migrated() {
+        let contents = "version = 1\nname = \"track\"";
+
+        let upgraded = upgrade::<File>(contents, MIGRATIONS).expect("valid");
+
+        assert_eq!(upgraded.migrated_from, None);
+        assert_eq!(upgraded.value.name, "track");
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let result = upgrade::<File>("version = 3", MIGRATIONS);
+
+        assert!(matches!(
+            result,
+            Err(UpgradeError::NewerVersion {
+                found: 3,
+                supported: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn non_integer_version_is_invalid() {
+        let result = upgrade::<File>(r#"version = "1""#, MIGRATIONS);
+
+        assert!(matches!(result, Err(UpgradeError::InvalidVersion)));
+    }
+
+    #[test]
+    fn negative_version_is_invalid() {
+        let result = upgrade::<File>("version = -1", MIGRATIONS);
+
+        assert!(matches!(result, Err(UpgradeError::InvalidVersion)));
+    }
+
+    #[test]
+    fn invalid_toml_is_a_parse_error() {
+        let result = upgrade::<File>("version = ", MIGRATIONS);
+
+        assert!(matches!(result, Err(UpgradeError::ParseError(_))));
+    }
+}
@@ -13,6 +13,8 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+pub mod status_table;
 pub mod tracking_info;
+pub mod user_templates;
 
 mod askama_filters;
@@ -26,3 +26,18 @@ pub fn bold(s: &str) -> askama::Result<String> {
 pub fn bright_black(s: &str) -> askama::Result<String> {
     Ok(s.bright_black().to_string())
 }
+
+/// Decorates the string in green.
+pub fn green(s: &str) -> askama::Result<String> {
+    Ok(s.green().to_string())
+}
+
+/// Decorates the string in yellow.
+pub fn yellow(s: &str) -> askama::Result<String> {
+    Ok(s.yellow().to_string())
+}
+
+/// Decorates the string in red.
+pub fn red(s: &str) -> askama::Result<String> {
+    Ok(s.red().to_string())
+}
@@ -0,0 +1,130 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use askama::Template;
+
+use crate::{
+    carrier::{FetchError, Shipment},
+    dates,
+};
+
+use super::askama_filters as filters;
+
+/// A compact view of the tracked parcels, with one line per parcel.
+#[derive(Debug, Default, Template)]
+#[template(path = "status_table")]
+pub struct StatusTable {
+    rows: Vec<StatusRow>,
+}
+
+/// A line of the status table.
+#[derive(Debug)]
+struct StatusRow {
+    status: Status,
+    description: String,
+    tracking_number: String,
+    age: String,
+    label: String,
+}
+
+/// The status of a parcel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Delivered,
+    InTransit,
+    Problem,
+}
+
+/// The separator between two columns.
+const COLUMN_SEPARATOR: &str = "  ";
+
+impl StatusTable {
+    /// Adds a line for a parcel.
+    pub fn push(
+        &mut self,
+        tracking_number: &str,
+        description: &str,
+        result: Result<&Shipment, &FetchError>,
+    ) {
+        self.rows
+            .push(StatusRow::new(tracking_number, description, result));
+    }
+
+    /// Returns whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns the width of the description column.
+    fn description_width(&self) -> usize {
+        self.width(|row| &row.description)
+    }
+
+    /// Returns the width of the tracking number column.
+    fn tracking_number_width(&self) -> usize {
+        self.width(|row| &row.tracking_number)
+    }
+
+    /// Returns the width of the age column.
+    fn age_width(&self) -> usize {
+        self.width(|row| &row.age)
+    }
+
+    /// Returns the width of a column.
+    fn width(&self, column: impl Fn(&StatusRow) -> &String) -> usize {
+        self.rows
+            .iter()
+            .map(|row| column(row).chars().count())
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl StatusRow {
+    /// Builds a line for a parcel.
+    fn new(
+        tracking_number: &str,
+        description: &str,
+        result: Result<&Shipment, &FetchError>,
+    ) -> Self {
+        let (status, age, label) = match result {
+            Ok(shipment) => {
+                let status = if shipment.problem {
+                    Status::Problem
+                } else if shipment.delivered {
+                    Status::Delivered
+                } else {
+                    Status::InTransit
+                };
+
+                match shipment.latest_event() {
+                    Some(event) => {
+                        (status, dates::age(event.date), event.label.clone())
+                    }
+                    None => (status, String::new(), String::new()),
+                }
+            }
+            Err(error) => (Status::Problem, String::new(), error.to_string()),
+        };
+
+        Self {
+            status,
+            description: description.to_owned(),
+            tracking_number: tracking_number.to_owned(),
+            age,
+            label,
+        }
+    }
+}
@@ -13,18 +13,202 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use askama::Template;
+use chrono::{DateTime, Local};
 use derive_new::new;
+use serde::Serialize;
 
-use crate::client::Event;
+use crate::{
+    carrier::{Event, FetchError, Shipment},
+    dates::DateFormatter,
+};
 
-use super::askama_filters as filters;
-
-/// A tracking info view.
-#[derive(new, Debug, Template)]
-#[template(path = "tracking_info")]
+/// A tracking info view, rendered by a `Renderer`.
+#[derive(new, Debug)]
 pub struct TrackingInfo<'a> {
     tracking_number: &'a str,
     description: Option<&'a str>,
-    events: &'a [Event],
+    result: Result<&'a Shipment, &'a FetchError>,
+    #[new(default)]
+    fetched_at: Option<DateTime<Local>>,
+    #[new(default)]
+    highlight_new: bool,
+    #[new(default)]
+    last_seen_event: Option<DateTime<Local>>,
+    #[new(default)]
+    dates: DateFormatter,
+}
+
+/// The context of a tracking info view in templates.
+#[derive(Debug, Serialize)]
+pub struct Context<'a> {
+    tracking_number: &'a str,
+    description: Option<&'a str>,
+    error: Option<String>,
+    cached_at: Option<String>,
+    summary: Option<String>,
+    delivered: bool,
+    timeline: Vec<StepContext<'a>>,
+    current_step: Option<&'a str>,
+    events: Vec<EventContext<'a>>,
+    url: Option<&'a str>,
+}
+
+/// A step in the context of a tracking info view.
+#[derive(Debug, Serialize)]
+pub struct StepContext<'a> {
+    label: &'a str,
+    reached: bool,
+}
+
+/// An event in the context of a tracking info view.
+#[derive(Debug, Serialize)]
+pub struct EventContext<'a> {
+    date: String,
+    label: &'a str,
+    code: Option<&'a str>,
+    new: bool,
+}
+
+impl TrackingInfo<'_> {
+    /// Marks the shipment as coming from the cache.
+    pub const fn cached(mut self, fetched_at: DateTime<Local>) -> Self {
+        self.fetched_at = Some(fetched_at);
+        self
+    }
+
+    /// Highlights the events more recent than the last seen one.
+    ///
+    /// If no event has been seen yet, all events are new.
+    pub const fn highlight_new_since(
+        mut self,
+        last_seen_event: Option<DateTime<Local>>,
+    ) -> Self {
+        self.highlight_new = true;
+        self.last_seen_event = last_seen_event;
+        self
+    }
+
+    /// Sets the formatter for the dates.
+    pub fn format_dates(mut self, dates: DateFormatter) -> Self {
+        self.dates = dates;
+        self
+    }
+
+    /// Returns whether the shipment has new events.
+    pub fn has_new_events(&self) -> bool {
+        match self.result {
+            Ok(shipment) => shipment.events.iter().any(|e| self.is_new(e)),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns whether an event is new.
+    fn is_new(&self, event: &Event) -> bool {
+        self.highlight_new
+            && self.last_seen_event.is_none_or(|date| event.date > date)
+    }
+
+    /// Builds the context for templates.
+    pub fn context(&self) -> Context<'_> {
+        let mut context = Context {
+            tracking_number: self.tracking_number,
+            description: self.description,
+            error: None,
+            cached_at: None,
+            summary: None,
+            delivered: false,
+            timeline: vec![],
+            current_step: None,
+            events: vec![],
+            url: None,
+        };
+
+        match self.result {
+            Ok(shipment) => {
+                context.cached_at =
+                    self.fetched_at.map(|date| self.format_date(&date));
+                context.summary = self.summary(shipment);
+                context.delivered = shipment.delivered;
+                context.timeline = shipment
+                    .timeline
+                    .iter()
+                    .map(|step| StepContext {
+                        label: &step.label,
+                        reached: step.reached,
+                    })
+                    .collect();
+                context.current_step =
+                    shipment.current_step().map(|step| step.label.as_str());
+                context.events = shipment
+                    .events
+                    .iter()
+                    .rev()
+                    .map(|event| EventContext {
+                        date: self.format_date(&event.date),
+                        label: &event.label,
+                        code: event.code.as_deref(),
+                        new: self.is_new(event),
+                    })
+                    .collect();
+                context.url = shipment.url.as_deref();
+            }
+            Err(error) => context.error = Some(self.error_message(error)),
+        }
+
+        context
+    }
+
+    /// Formats a date.
+    fn format_date(&self, date: &DateTime<Local>) -> String {
+        self.dates.format(*date)
+    }
+
+    /// Returns a summary of the product, route and delivery date, if known.
+    fn summary(&self, shipment: &Shipment) -> Option<String> {
+        let product = shipment.product.as_deref().map(capitalise);
+        let route = match (&shipment.origin_country, &shipment.arrival_country)
+        {
+            (Some(origin), Some(arrival)) => {
+                Some(format!("from {origin} to {arrival}"))
+            }
+            _ => None,
+        };
+        let delivery = shipment.delivery_date.map(|date| {
+            let date = self.format_date(&date);
+            if shipment.delivered {
+                format!("delivered on {date}")
+            } else if shipment.is_final {
+                format!("not delivered, closed on {date}")
+            } else {
+                format!("expected on {date}")
+            }
+        });
+
+        let shipping =
+            [product, route].into_iter().flatten().collect::<Vec<_>>();
+        let shipping = (!shipping.is_empty()).then(|| shipping.join(" "));
+
+        let summary = [shipping, delivery]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        (!summary.is_empty()).then(|| capitalise(&summary.join(", ")))
+    }
+
+    /// Returns the message for an error, with the message from the API if any.
+    fn error_message(&self, error: &FetchError) -> String {
+        match error.api_message() {
+            Some(message) => format!("Error: {error} ({message})"),
+            None => format!("Error: {error}"),
+        }
+    }
+}
+
+/// Capitalises the first letter of a string.
+fn capitalise(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
 }
@@ -0,0 +1,120 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Templates rendered at runtime, which can be overridden by the user.
+//!
+//! The default templates are embedded in `track`. A template with the same name
+//! in the templates directory of the configuration directory replaces them.
+
+use std::{fs, io, path::PathBuf};
+
+use colored::Colorize;
+use minijinja::Environment;
+use thiserror::Error;
+
+use crate::config::{self, ConfigDirError};
+
+use super::tracking_info::TrackingInfo;
+
+/// The default tracking info template.
+pub const DEFAULT_TRACKING_INFO: &str =
+    include_str!("../../templates/tracking_info.jinja");
+
+/// The name of the tracking info template.
+pub const TRACKING_INFO: &str = "tracking_info.jinja";
+
+/// The name of the templates directory, in the configuration directory.
+const TEMPLATES_DIR_NAME: &str = "templates";
+
+/// A renderer for views, using the user templates if present.
+#[derive(Debug)]
+pub struct Renderer {
+    env: Environment<'static>,
+    user_template: bool,
+}
+
+/// An error that can occur when loading the user templates.
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("impossible to locate the templates directory")]
+    NoConfigDir(#[from] ConfigDirError),
+    #[error("error while reading the template {0}")]
+    ReadError(PathBuf, #[source] io::Error),
+    #[error("invalid template {0}")]
+    InvalidTemplate(PathBuf, #[source] minijinja::Error),
+}
+
+/// An error that can occur when rendering a view.
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("error while rendering the built-in template {0}")]
+    BuiltInError(String, #[source] minijinja::Error),
+    #[error("error while rendering the template {0}")]
+    UserTemplateError(String, #[source] minijinja::Error),
+}
+
+impl Renderer {
+    /// Loads the templates, preferring the user ones.
+    pub fn load() -> Result<Self, LoadError> {
+        let path = templates_dir()?.join(TRACKING_INFO);
+
+        let mut env = Environment::new();
+        env.add_filter("bold", |s: String| s.bold().to_string());
+        env.add_filter("bright_black", |s: String| {
+            s.bright_black().to_string()
+        });
+        env.add_filter("red", |s: String| s.red().to_string());
+        env.add_filter("green", |s: String| s.green().to_string());
+
+        let user_template = match fs::read_to_string(&path) {
+            Ok(source) => {
+                env.add_template_owned(TRACKING_INFO, source)
+                    .map_err(|e| LoadError::InvalidTemplate(path, e))?;
+                true
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                env.add_template(TRACKING_INFO, DEFAULT_TRACKING_INFO)
+                    .expect("the default template is valid");
+                false
+            }
+            Err(e) => return Err(LoadError::ReadError(path, e)),
+        };
+
+        Ok(Self { env, user_template })
+    }
+
+    /// Renders a tracking info view.
+    pub fn render(
+        &self,
+        view: &TrackingInfo<'_>,
+    ) -> Result<String, RenderError> {
+        self.env
+            .get_template(TRACKING_INFO)
+            .and_then(|template| template.render(view.context()))
+            .map_err(|e| {
+                let name = TRACKING_INFO.to_owned();
+                if self.user_template {
+                    RenderError::UserTemplateError(name, e)
+                } else {
+                    RenderError::BuiltInError(name, e)
+                }
+            })
+    }
+}
+
+/// Gets the directory of the user templates.
+pub fn templates_dir() -> Result<PathBuf, ConfigDirError> {
+    Ok(config::config_dir()?.join(TEMPLATES_DIR_NAME))
+}
@@ -0,0 +1,297 @@
+// track - A quick-and-dirty CLI tool for tracking parcels.
+// Copyright (C) 2020, 2022 Jean-Philippe Cugnet <jean-philippe@cugnet.eu>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Runs `track` against a local fixture of the La Poste API.
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    process::{self, Command, Output},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const TRACKING_NUMBER: &str = "RR123456785FR";
+
+const SHIPMENT: &str = r#"{
+    "returnCode": 200,
+    "shipment": {
+        "idShip": "RR123456785FR",
+        "product": "lettre",
+        "isFinal": false,
+        "event": [{
+            "code": "PC1",
+            "date": "2023-05-30T10:00:00+02:00",
+            "label": "Pris en charge"
+        }]
+    }
+}"#;
+
+/// A local HTTP server replying with canned responses, one per connection.
+struct Fixture {
+    url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    thread: JoinHandle<()>,
+}
+
+impl Fixture {
+    /// Starts a server replying with `responses`, in order.
+    fn serve(responses: Vec<String>) -> Self {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").expect("a free local port");
+        let url = format!(
+            "http://{}/suivi/v2",
+            listener.local_addr().expect("a bound address")
+        );
+
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let thread = thread::spawn({
+            let requests = Arc::clone(&requests);
+            move || {
+                for response in responses {
+                    let mut stream = accept(&listener);
+
+                    let mut request = String::new();
+                    let mut reader = BufReader::new(&stream);
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).expect("a request line");
+                        if line.trim_end().is_empty() {
+                            break;
+                        }
+                        request.push_str(&line);
+                    }
+                    requests.lock().expect("unpoisoned").push(request);
+
+                    stream
+                        .write_all(response.as_bytes())
+                        .expect("a written response");
+                }
+            }
+        });
+
+        Self {
+            url,
+            requests,
+            thread,
+        }
+    }
+
+    /// Waits for all the responses to be sent, and returns the requests.
+    fn requests(self) -> Vec<String> {
+        self.thread
+            .join()
+            .expect("the fixture to run without panicking");
+        Arc::try_unwrap(self.requests)
+            .expect("a single owner")
+            .into_inner()
+            .expect("unpoisoned")
+    }
+}
+
+/// The maximum time to wait for `track` to connect to the fixture.
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accepts a connection, failing instead of blocking after `ACCEPT_TIMEOUT`.
+fn accept(listener: &TcpListener) -> TcpStream {
+    listener
+        .set_nonblocking(true)
+        .expect("a non-blocking listener");
+    let deadline = Instant::now() + ACCEPT_TIMEOUT;
+
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false).expect("a blocking stream");
+                return stream;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                assert!(Instant::now() < deadline, "track did not connect");
+                thread::sleep(Duration::from_millis(10));
+            }
+            Err(e) => panic!("failed to accept a connection: {e}"),
+        }
+    }
+}
+
+/// Builds an HTTP response with a JSON body.
+fn response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    let mut response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n",
+        body.len()
+    );
+    for (name, value) in headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str("\r\n");
+    response.push_str(body);
+    response
+}
+
+/// A temporary home for the configuration, state and cache of `track`.
+struct Home(PathBuf);
+
+impl Home {
+    /// Creates a home with a configuration for the tests.
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir()
+            .join(format!("track-test-{}-{name}", process::id()));
+        let config_dir = path.join("config").join("track");
+        fs::create_dir_all(&config_dir).expect("a config directory");
+        fs::write(
+            config_dir.join("config.toml"),
+            "version = 1\napi_key = \"key\"\nretry_delay = \"10ms\"\n",
+        )
+        .expect("a config file");
+
+        Self(path)
+    }
+
+    /// Runs `track` with `args`, using the API at `api_url`.
+    fn track(&self, api_url: &str, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_track"))
+            .args(args)
+            .env("TRACK_API_URL", api_url)
+            .env("HOME", &self.0)
+            .env("XDG_CONFIG_HOME", self.0.join("config"))
+            .env("XDG_DATA_HOME", self.0.join("data"))
+            .env("XDG_CACHE_HOME", self.0.join("cache"))
+            .env_remove("NO_COLOR")
+            .env_remove("CLICOLOR_FORCE")
+            .output()
+            .expect("track to run")
+    }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn info_fetches_the_tracking_info_from_the_api_url() {
+    let home = Home::new("info");
+    let fixture = Fixture::serve(vec![response("200 OK", &[], SHIPMENT)]);
+
+    let output = home.track(&fixture.url, &["info", TRACKING_NUMBER]);
+    let requests = fixture.requests();
+
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Pris en charge"), "{stdout}");
+
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0]
+        .starts_with(&format!("GET /suivi/v2/idships/{TRACKING_NUMBER} ")));
+    assert!(requests[0].to_lowercase().contains("x-okapi-key: key"));
+}
+
+#[test]
+fn info_reports_unknown_parcels() {
+    let home = Home::new("unknown");
+    let fixture = Fixture::serve(vec![response(
+        "404 Not Found",
+        &[],
+        r#"{"returnCode": 404, "returnMessage": "Numéro introuvable"}"#,
+    )]);
+
+    let output = home.track(&fixture.url, &["info", TRACKING_NUMBER]);
+    fixture.requests();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Numéro introuvable"), "{stderr}");
+}
+
+#[test]
+fn transient_errors_are_retried_after_the_retry_after_delay() {
+    let home = Home::new("retry");
+    let fixture = Fixture::serve(vec![
+        response("503 Service Unavailable", &[("Retry-After", "1")], "{}"),
+        response("200 OK", &[], SHIPMENT),
+    ]);
+
+    let start = Instant::now();
+    let output = home.track(&fixture.url, &["info", TRACKING_NUMBER]);
+    let elapsed = start.elapsed();
+    let requests = fixture.requests();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(requests.len(), 2);
+    assert!(
+        elapsed >= Duration::from_secs(1),
+        "retried after {elapsed:?}"
+    );
+}
+
+#[test]
+fn transient_errors_are_retried_with_a_backoff() {
+    let home = Home::new("backoff");
+    let fixture = Fixture::serve(vec![
+        response("500 Internal Server Error", &[], "{}"),
+        response("429 Too Many Requests", &[], "{}"),
+        response("200 OK", &[], SHIPMENT),
+    ]);
+
+    let output = home.track(&fixture.url, &["info", TRACKING_NUMBER]);
+    let requests = fixture.requests();
+
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(requests.len(), 3);
+}
+
+#[test]
+fn all_fetches_the_parcels_in_one_batch() {
+    let home = Home::new("batch");
+    let batch = format!(
+        r#"[
+            {SHIPMENT},
+            {{
+                "returnCode": 404,
+                "returnMessage": "Numéro introuvable",
+                "idShip": "RR000000005FR"
+            }}
+        ]"#
+    );
+    let fixture =
+        Fixture::serve(vec![response("207 Multi-Status", &[], &batch)]);
+
+    for (tracking_number, description) in
+        [(TRACKING_NUMBER, "Book"), ("RR000000005FR", "Shoes")]
+    {
+        let output =
+            home.track(&fixture.url, &["add", tracking_number, description]);
+        assert!(output.status.success(), "{output:?}");
+    }
+
+    let output = home.track(&fixture.url, &["all"]);
+    let requests = fixture.requests();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Pris en charge"), "{stdout}");
+    assert!(stdout.contains("Numéro introuvable"), "{stdout}");
+
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].contains(TRACKING_NUMBER));
+    assert!(requests[0].contains("RR000000005FR"));
+}